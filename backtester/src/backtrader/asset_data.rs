@@ -1,16 +1,37 @@
+use futures::StreamExt;
 use polars::frame::DataFrame;
-use crate::data::data::{DataHandler, DataHandlerFetch};
+use polars::prelude::{PolarsError, PolarsResult};
+use crate::backtrader::exchange::Exchange;
+use crate::backtrader::exchange_profile::SymbolRules;
+use crate::backtrader::funding::FundingRates;
+use crate::backtrader::margin::MarginConfig;
+use crate::backtrader::order::{Liquidity, Side};
+use crate::data::candle::{candles_to_dataframe, Candle};
+use crate::data::source::{DataSource, DateRange};
+use crate::data::stream::CandleStream;
+
+// Streamed candles are buffered and appended to an asset's data as one chunk of this many
+const STREAM_BATCH: usize = 1024;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone)] // Derive necessary traits
 pub struct AssetData {
     pub symbol: String,                  // Ticker symbol of the asset
     pub cash: f64,                       // Available cash allocated to this asset
-    pub positions: f64,                  // Number of positions held for this asset
+    pub positions: f64,                  // Number of positions held for this asset, negative when short
     pub position_value: f64,             // Current value of the positions
     pub total_value: f64,                // Total value of the asset (cash + positions)
     pub history: Vec<f64>,               // History of total values over time
+    pub margin: Option<MarginConfig>,    // Cash only and long only without margin
+    pub borrow_fees: f64,                // Borrow fees paid on shorts and leveraged longs so far
+    pub exposed_bars: usize,             // Bars that closed with an open position
+    pub rules: SymbolRules,              // Quantity step, price tick and minimum size fills are held to
+    pub funding: Option<FundingRates>,   // Perpetual futures settle funding on open positions, spot does not
+    pub funding_pnl: f64,                // Funding received so far, negative when it was mostly paid
+    pub source: Option<DataSource>,      // Where `load_data` reads the candles from
+    pub loaded_range: Option<DateRange>, // Window the data was loaded for, None when it was set directly
     data: Option<DataFrame>,         // DataFrame holding asset-specific price and signal history
+    streamed: Vec<Candle>,           // Streamed candles not appended to `data` yet
 }
 
 impl AssetData {
@@ -22,20 +43,125 @@ impl AssetData {
             position_value,
             total_value: cash,
             history: vec!(),
+            margin: None,
+            borrow_fees: 0.0,
+            exposed_bars: 0,
+            rules: SymbolRules::UNCONSTRAINED,
+            funding: None,
+            funding_pnl: 0.0,
+            source: None,
+            loaded_range: None,
             data: None,
+            streamed: vec!(),
         }
     }
 
-    pub fn load_data(&mut self) {
-        /* TODO load by coherence with exchange */
-        self.data = Some(DataHandler::load_data("examples/data/btcusd_2-min_data.csv"));
+    pub fn load_data(&mut self, window: DateRange) -> PolarsResult<()> {
+        let Some(source) = &self.source else {
+            return Err(PolarsError::ComputeError(format!("No data source configured for '{}'", self.symbol).into()));
+        };
+        self.data = Some(source.load_between(window)?);
+        self.loaded_range = Some(window);
+        Ok(())
+    }
+
+    // Back to `cash` and no position, keeping the data and the instrument settings, for the next backtest
+    pub fn reset(&mut self, cash: f64) {
+        self.cash = cash;
+        self.positions = 0.0;
+        self.position_value = 0.0;
+        self.total_value = cash;
+        self.history.clear();
+        self.borrow_fees = 0.0;
+        self.exposed_bars = 0;
+        self.funding_pnl = 0.0;
+    }
+
+    pub fn set_source(&mut self, source: DataSource) {
+        self.source = Some(source);
+    }
+
+    // Use an already loaded frame instead of reading from disk, e.g. for tests or data fetched elsewhere
+    pub fn set_data(&mut self, data: DataFrame) {
+        self.data = Some(data);
+        self.streamed.clear();
+        self.loaded_range = None;
+    }
+
+    // Funding rates loaded next to the OHLCV frame make the asset a perpetual future
+    pub fn set_funding(&mut self, funding: FundingRates) {
+        self.funding = Some(funding);
+    }
+
+    pub const fn set_rules(&mut self, rules: SymbolRules) {
+        self.rules = rules;
     }
 
-    pub fn get_data(&self) -> &Option<DataFrame> {
+    pub const fn set_margin(&mut self, margin: MarginConfig) {
+        self.margin = Some(margin);
+    }
+
+    pub fn equity(&self, price: f64) -> f64 {
+        self.cash + self.positions * price
+    }
+
+    /// Largest quantity an order on `side` can trade at `price`, reducing a position is always allowed.
+    /// `cash` is what the asset may spend, its own cash unless it buys from a shared pool.
+    /// Commission is reserved at the rate a fill with `liquidity` pays at `timestamp`.
+    pub fn max_order_quantity(&self, side: Side, price: f64, cash: f64, liquidity: Liquidity, timestamp: i64, exchange: &Exchange) -> f64 {
+        let max_trade_value = |cash: f64| exchange.max_trade_value(&self.symbol, liquidity, timestamp, cash);
+        match (self.margin, side) {
+            (None, Side::Buy) => max_trade_value(cash) / price,
+            (None, Side::Sell) => self.positions.max(0.0),
+            (Some(margin), side) => {
+                // Exposure is capped at equity / initial margin in either direction
+                let buying_power = (cash + self.positions * price).max(0.0) / margin.initial_margin;
+                let (headroom, reducing) = match side {
+                    Side::Buy => (buying_power - self.positions * price, (-self.positions).max(0.0)),
+                    Side::Sell => (buying_power + self.positions * price, self.positions.max(0.0)),
+                };
+                (max_trade_value(headroom) / price).max(reducing)
+            }
+        }
+    }
+
+    pub const fn get_data(&self) -> &Option<DataFrame> {
         &self.data
     }
 
-    pub fn load_latest_candle() {
-        /* TODO implement streaming/yielding? */
+    // Wait for the stream's next candle and append it to the asset's data, None once the stream has ended.
+    // Candles reach the data in batches of `STREAM_BATCH`, the rest of them once the stream ends.
+    pub async fn load_latest_candle(&mut self, stream: &mut CandleStream) -> PolarsResult<Option<Candle>> {
+        let Some(candle) = stream.next().await else {
+            self.append_streamed()?;
+            return Ok(None);
+        };
+        self.streamed.push(candle);
+        if self.streamed.len() >= STREAM_BATCH {
+            self.append_streamed()?;
+        }
+        Ok(Some(candle))
+    }
+
+    fn append_streamed(&mut self) -> PolarsResult<()> {
+        if self.streamed.is_empty() {
+            return Ok(());
+        }
+        let batch = candles_to_dataframe(&self.streamed)?;
+        self.streamed.clear();
+        self.data = Some(match self.data.take() {
+            Some(mut data) => {
+                data.vstack_mut(&batch)?;
+                // Merged into one chunk whenever the batches outgrow what was merged before,
+                // so every row is copied a constant number of times however long the stream runs
+                let merged = data.get_columns()[0].as_materialized_series().chunk_lengths().next().unwrap_or(0);
+                if data.height() >= 2 * merged {
+                    data.as_single_chunk_par();
+                }
+                data
+            }
+            None => batch,
+        });
+        Ok(())
     }
 }
\ No newline at end of file
@@ -1,86 +1,616 @@
 use polars::prelude::*;
-use std::collections::HashMap;
-use polars::export::num::CheckedSub;
+use std::collections::{BTreeSet, HashMap};
 use crate::backtrader::asset_data::AssetData;
 use crate::backtrader::exchange::Exchange;
-use crate::performance::performance::{calculate_annualized_yearly_return, calculate_annualized_volatility, calculate_daily_returns, calculate_total_return};
-use crate::strategy::strategy::{StrategyTrait};
+use crate::backtrader::ledger::{equity_curve_to_dataframe, Rejection, Trade, TradeLedger};
+use crate::backtrader::margin::{Liquidation, MarginConfig};
+use crate::backtrader::exchange_profile::{ExchangeProfile, SymbolRules, CUSTOM_EXCHANGE};
+use crate::backtrader::fees::{FeeSchedule, VolumeTracker};
+use crate::backtrader::funding::{funding_amount, FundingPayment, FundingRates};
+use crate::backtrader::exits::{Bracket, Exit, ExitReason, ExitRules, RollingAtr};
+use crate::backtrader::order::{Liquidity, Order, OrderColumns, OrderType, Side, TimeInForce};
+use crate::backtrader::order_book::{Fill, OrderBook, OrderId};
+use crate::backtrader::rebalance::{rebalance_quantities, weight_drift, RebalanceSchedule};
+use crate::backtrader::slippage::SlippageModel;
+use crate::backtrader::position_sizer::{PositionSizer, SizingContext};
+use crate::data::candle::{Candle, CandleColumns};
+use crate::data::source::{DataSource, DateRange};
+use crate::data::stream::CandleStream;
+use crate::performance::benchmark::{benchmark_equity, close_prices, Benchmark, BenchmarkMetrics};
+use crate::performance::frequency::{annualization_curve, BarFrequency};
+use crate::performance::performance::calculate_underwater_curve;
+use crate::performance::report::{aggregate_equity, PerformanceMetrics, PerformanceReport};
+use crate::strategy::indicators::average_true_range;
+use crate::strategy::event::{EventStrategy, StrategyContext};
+use crate::strategy::strategy::{normalize_signals, SignalKind, StrategyTrait, EXPOSURE_COLUMN, WEIGHT_PREFIX};
 
-pub type PortfolioHistory = HashMap<String, Vec<f64>>;
-pub type DailyPortfolioValues = HashMap<String, Vec<f64>>;
+pub type PortfolioHistory = HashMap<String, Vec<(i64, f64)>>; // Timestamp and total value per bar
+
+const CASH_DUST: f64 = 1e-9;
+const POSITION_DUST: f64 = 1e-12;
+// Fills forced by a liquidation are not backed by an order, order ids start at 1
+pub const LIQUIDATION_ORDER_ID: OrderId = 0;
+// Fills of stop losses, take profits and time exits, which are not in the order book either
+pub const EXIT_ORDER_ID: OrderId = OrderId::MAX;
+
+// What the backtest loop keeps of a symbol's signals between its bars
+#[derive(Debug, Clone, Copy, Default)]
+struct SignalState {
+    order: Option<OrderId>, // The strategy's pending order, cancel/replaced by the next signal
+    last: i8,               // Signal of the symbol's previous bar, only a change trades
+    exposure: f64,          // Target exposure of the previous bar, for strategies that emit one
+}
+
+// Column views of a prepared signal frame, read bar by bar in the backtest loop
+struct BarColumns<'a> {
+    candles: CandleColumns<'a>,
+    signals: &'a Int8Chunked,
+    signal_kind: SignalKind,
+    exposure: Option<&'a Float64Chunked>, // Target exposure, when the strategy sizes its own positions
+    orders: OrderColumns<'a>,
+    atr: Option<&'a Float64Chunked>,
+    exit_atr: Option<&'a Float64Chunked>,
+}
+
+impl<'a> BarColumns<'a> {
+    fn new(df: &'a DataFrame, signal_kind: SignalKind) -> PolarsResult<Self> {
+        let atr = if df.schema().contains("atr") { Some(df.column("atr")?.f64()?) } else { None };
+        let exit_atr = if df.schema().contains("exit_atr") { Some(df.column("exit_atr")?.f64()?) } else { None };
+        Ok(Self {
+            candles: CandleColumns::new(df)?,
+            signals: df.column("signal")?.i8()?,
+            signal_kind,
+            exposure: if df.schema().contains(EXPOSURE_COLUMN) { Some(df.column(EXPOSURE_COLUMN)?.f64()?) } else { None },
+            orders: OrderColumns::new(df)?,
+            atr,
+            exit_atr,
+        })
+    }
+
+    // ATR as of the previous close, what is known when a position is entered at bar `i`
+    fn exit_atr_before(&self, i: usize) -> Option<f64> {
+        self.exit_atr.zip(i.checked_sub(1)).and_then(|(atr, previous)| atr.get(previous))
+    }
+}
 
 #[derive(Debug)]
 pub struct Backtrader {
     initial_capital: f64,
     exchange: Exchange,
     assets_data: HashMap<String, AssetData>, // Asset data keyed by asset symbol.
-    portfolio_history: PortfolioHistory,    // Historical total values of all assets.
-    daily_portfolio_values: DailyPortfolioValues, // Total portfolio value over time.
+    portfolio_history: PortfolioHistory,    // Timestamped total values of all assets.
+    order_book: OrderBook, // Pending orders per symbol and every fill so far.
+    position_sizer: Option<Box<dyn PositionSizer>>, // Buys go all in without one.
+    liquidations: Vec<Liquidation>, // Positions closed for breaching their maintenance margin.
+    funding_payments: Vec<FundingPayment>, // Funding settled on perpetual futures positions.
+    exit_rules: Option<ExitRules>, // Stop loss, take profit, trailing and time exits attached to every position.
+    brackets: HashMap<String, Bracket>, // Exit levels of the open position per symbol.
+    exits: Vec<Exit>, // Positions closed by their exit rules.
+    exit_atrs: HashMap<String, RollingAtr>, // ATR for exits per symbol in backtests that see one bar at a time.
+    ledger: TradeLedger, // Round trip trades built from the fills.
+    risk_free_rate: f64, // Yearly rate used for Sharpe and Sortino ratios.
+    benchmark: Option<Benchmark>, // Reference the report's alpha, beta and capture ratios are measured against.
+    shared_cash: bool, // Every symbol buys from one cash pool instead of its own slice, only while `backtest_portfolio` or `backtest_rebalance` runs.
 }
 
 
 impl Backtrader {
     // No self parameter here, as new creates a new instance
+    // Trades on a custom exchange with a flat commission and no order constraints, see `with_exchange` for real venues
     pub fn new(initial_capital: f64, commission_pct: f64, commission_fixed: f64, symbols: Vec<&String>) -> Self {
         let mut assets_data: HashMap<String, AssetData> = HashMap::new();
-        let mut portfolio_history: HashMap<String, Vec<f64>> = HashMap::new();
-        let mut daily_portfolio_values: HashMap<String, Vec<f64>> = HashMap::new();
+        let mut portfolio_history: PortfolioHistory = HashMap::new();
         let symbol_capital = initial_capital / symbols.len() as f64;
         for symbol in symbols {
             let asset_data = AssetData::new(symbol.as_str(), symbol_capital, 0.0, 0.0);
             assets_data.insert(symbol.clone(), asset_data.clone());
             portfolio_history.insert(symbol.clone(), vec![]);
-            daily_portfolio_values.insert(symbol.clone(), vec![]);
         }
 
-        /* TODO Implement changing Exchange and make data fetching exchange dependant */
-
         Self {
             initial_capital,
-            exchange: Exchange {
-                name: "Binance".to_string(),
-                commission_pct,
-                commission_fixed,
-            },
+            exchange: ExchangeProfile::custom(CUSTOM_EXCHANGE, commission_pct, commission_fixed).exchange(),
             assets_data: assets_data.clone(),
             portfolio_history,
-            daily_portfolio_values: daily_portfolio_values.clone(),
+            order_book: OrderBook::new(),
+            position_sizer: None,
+            liquidations: vec![],
+            funding_payments: vec![],
+            exit_rules: None,
+            brackets: HashMap::new(),
+            exits: vec![],
+            exit_atrs: HashMap::new(),
+            ledger: TradeLedger::new(),
+            risk_free_rate: 0.0,
+            benchmark: None,
+            shared_cash: false,
         }
     }
 
+    /// Trade on an exchange profile, e.g. `ExchangeProfile::binance()`, with its fees, order constraints and hours.
+    /// Fails when the exchange does not list one of the symbols.
+    pub fn with_exchange(initial_capital: f64, profile: &ExchangeProfile, symbols: Vec<&String>) -> PolarsResult<Self> {
+        if let Some(symbol) = symbols.iter().find(|symbol| !profile.supports(symbol)) {
+            return Err(PolarsError::ComputeError(format!("Symbol '{}' is not listed on {}", symbol, profile.name).into()));
+        }
 
+        let mut backtrader = Self::new(initial_capital, 0.0, 0.0, symbols);
+        backtrader.exchange = profile.exchange();
+        for (symbol, asset) in backtrader.assets_data.iter_mut() {
+            asset.set_rules(profile.rules(symbol));
+        }
+        Ok(backtrader)
+    }
+
+    pub fn exchange_name(&self) -> &str {
+        &self.exchange.name
+    }
+
+    // Provide the price history for a symbol up front instead of loading it in `backtest`
+    pub fn set_data(&mut self, symbol: &str, data: DataFrame) {
+        match self.assets_data.get_mut(symbol) {
+            Some(asset) => asset.set_data(data),
+            None => eprintln!("Asset '{}' not found in portfolio, cannot set data.", symbol),
+        }
+    }
+
+    pub fn get_asset(&self, symbol: &str) -> Option<&AssetData> {
+        self.assets_data.get(symbol)
+    }
+
+    pub const fn order_book(&self) -> &OrderBook {
+        &self.order_book
+    }
+
+    // Submit, cancel or replace orders outside of the strategy's signals
+    pub const fn order_book_mut(&mut self) -> &mut OrderBook {
+        &mut self.order_book
+    }
+
+    pub fn fills(&self) -> &[Fill] {
+        self.order_book.fills()
+    }
+
+    pub const fn set_risk_free_rate(&mut self, risk_free_rate: f64) {
+        self.risk_free_rate = risk_free_rate;
+    }
+
+    pub fn set_benchmark(&mut self, benchmark: Benchmark) {
+        self.benchmark = Some(benchmark);
+    }
+
+    // Benchmark equity for the capital of a symbol, or of the whole portfolio when `symbol` is None
+    fn benchmark_equity(&self, symbol: Option<&str>) -> PolarsResult<Option<Vec<(i64, f64)>>> {
+        let symbol_capital = self.initial_capital / self.assets_data.len() as f64;
+        match (&self.benchmark, symbol) {
+            (None, _) => Ok(None),
+            (Some(Benchmark::Prices(prices)), Some(_)) => Ok(Some(benchmark_equity(prices, symbol_capital))),
+            (Some(Benchmark::Prices(prices)), None) => Ok(Some(benchmark_equity(prices, self.initial_capital))),
+            (Some(Benchmark::BuyAndHold), Some(symbol)) => {
+                let mut prices = match self.assets_data.get(symbol).and_then(|asset| asset.get_data().as_ref()) {
+                    Some(data) => close_prices(data)?,
+                    None => vec![],
+                };
+                // Held over the backtested bars only, not the whole frame
+                let history = self.portfolio_history.get(symbol).map_or(&[][..], |history| history.as_slice());
+                if let (Some(&(first, _)), Some(&(last, _))) = (history.first(), history.last()) {
+                    prices.retain(|&(timestamp, _)| timestamp >= first && timestamp <= last);
+                }
+                Ok(Some(benchmark_equity(&prices, symbol_capital)))
+            }
+            (Some(Benchmark::BuyAndHold), None) => {
+                let mut curves = vec![];
+                for symbol in self.assets_data.keys() {
+                    curves.extend(self.benchmark_equity(Some(symbol))?);
+                }
+                let curves: Vec<(&[(i64, f64)], f64)> = curves.iter().map(|curve| (curve.as_slice(), symbol_capital)).collect();
+                Ok(Some(aggregate_equity(&curves)))
+            }
+        }
+    }
+
+    pub const fn ledger(&self) -> &TradeLedger {
+        &self.ledger
+    }
+
+    // Equity curve of a symbol with one timestamped row per bar
+    pub fn equity_curve(&self, symbol: &str) -> PolarsResult<DataFrame> {
+        equity_curve_to_dataframe(self.portfolio_history.get(symbol).map_or(&[], |history| history.as_slice()))
+    }
+
+    // Drawdown from the running peak of the symbol's equity curve at every bar, 0 at new highs
+    pub fn underwater_curve(&self, symbol: &str) -> PolarsResult<DataFrame> {
+        let equity = self.equity_curve(symbol)?;
+        let drawdown = calculate_underwater_curve(equity.column("equity")?.as_materialized_series())?;
+        equity.select(["timestamp"])?.hstack(&[drawdown.into()])
+    }
+
+    fn record_fill(&mut self, fill: Fill) {
+        self.ledger.record_fill(&fill);
+        self.order_book.record_fill(fill);
+    }
+
+    // Charge maker/taker fees by 30 day volume tier instead of the flat commission
+    pub fn set_fee_schedule(&mut self, fees: FeeSchedule) {
+        self.exchange.fees = Some(fees);
+    }
+
+    pub fn set_slippage_model(&mut self, slippage: impl SlippageModel + 'static) {
+        self.exchange.slippage = Some(Box::new(slippage));
+    }
+
+    pub fn set_position_sizer(&mut self, position_sizer: impl PositionSizer + 'static) {
+        self.position_sizer = Some(Box::new(position_sizer));
+    }
+
+    // Apply margin terms to a symbol, which also allows it to go short
+    pub fn set_margin(&mut self, symbol: &str, margin: MarginConfig) {
+        match self.assets_data.get_mut(symbol) {
+            Some(asset) => asset.set_margin(margin),
+            None => eprintln!("Asset '{}' not found in portfolio, cannot set margin.", symbol),
+        }
+    }
+
+    // Instrument metadata for a symbol traded outside the built in exchange profiles
+    pub fn set_rules(&mut self, symbol: &str, rules: SymbolRules) {
+        match self.assets_data.get_mut(symbol) {
+            Some(asset) => asset.set_rules(rules),
+            None => eprintln!("Asset '{}' not found in portfolio, cannot set rules.", symbol),
+        }
+    }
+
+    // Trade a symbol as a perpetual future that settles these funding rates
+    pub fn set_funding_rates(&mut self, symbol: &str, funding: FundingRates) {
+        match self.assets_data.get_mut(symbol) {
+            Some(asset) => asset.set_funding(funding),
+            None => eprintln!("Asset '{}' not found in portfolio, cannot set funding rates.", symbol),
+        }
+    }
+
+    // Read the symbol's candles from this source when a backtest needs them
+    pub fn set_data_source(&mut self, symbol: &str, source: DataSource) {
+        match self.assets_data.get_mut(symbol) {
+            Some(asset) => asset.set_source(source),
+            None => eprintln!("Asset '{}' not found in portfolio, cannot set data source.", symbol),
+        }
+    }
+
+    // Sources keyed by symbol, e.g. from `DataSource::from_file`
+    pub fn set_data_sources(&mut self, sources: HashMap<String, DataSource>) {
+        for (symbol, source) in sources {
+            self.set_data_source(&symbol, source);
+        }
+    }
+
+    // The symbol's candles within `range` for one run, loaded from its data source unless they were set directly.
+    // Data sources only read the bars inside the range, data set directly is filtered on a copy
+    // so a later run with a wider range still sees all of it.
+    fn run_data(&mut self, symbol: &str, range: DateRange) -> PolarsResult<DataFrame> {
+        let Some(asset) = self.assets_data.get_mut(symbol) else {
+            return Err(PolarsError::ComputeError(format!("Asset '{}' not found in portfolio.", symbol).into()));
+        };
+        let stale = asset.loaded_range.is_some_and(|window| window != range);
+        if let (Some(data), false) = (asset.get_data(), stale) {
+            return match (asset.loaded_range, range.predicate()) {
+                (None, Some(predicate)) => data.clone().lazy().filter(predicate).collect(),
+                _ => Ok(data.clone()),
+            };
+        }
+        if let Some(exchange) = asset.source.as_ref().and_then(|source| source.exchange.as_ref()) {
+            // Candles of another venue would be traded with this exchange's fees, hours and rules
+            if self.exchange.name != CUSTOM_EXCHANGE && !exchange.eq_ignore_ascii_case(&self.exchange.name) {
+                return Err(PolarsError::ComputeError(
+                    format!("Data of '{}' comes from {}, the backtest trades on {}", symbol, exchange, self.exchange.name).into(),
+                ));
+            }
+        }
+        asset.load_data(range)?;
+        Ok(asset.get_data().clone().unwrap())
+    }
+
+    // Attach exits to every position opened from now on, they are evaluated before the strategy's signals
+    pub const fn set_exit_rules(&mut self, exit_rules: ExitRules) {
+        self.exit_rules = Some(exit_rules);
+    }
+
+    pub fn exits(&self) -> &[Exit] {
+        &self.exits
+    }
+
+    pub fn funding_payments(&self) -> &[FundingPayment] {
+        &self.funding_payments
+    }
+
+    pub fn liquidations(&self) -> &[Liquidation] {
+        &self.liquidations
+    }
+
+    pub fn rejections(&self) -> &[Rejection] {
+        self.ledger.rejections()
+    }
+
+    // Units to order for a signal, None leaves it to `execute_trade` to use everything available.
+    // Sells only get sized when the asset is on margin, otherwise they flatten the position.
+    fn order_quantity(&self, symbol: &str, side: Side, price: f64, atr: Option<f64>) -> Option<f64> {
+        let position_sizer = self.position_sizer.as_ref()?;
+        let asset = self.assets_data.get(symbol)?;
+        let context = SizingContext {
+            asset,
+            price,
+            equity: self.sizing_equity(symbol, price),
+            atr,
+        };
+        let target = position_sizer.size(&context);
+        match side {
+            Side::Buy => Some((target - asset.positions).max(0.0)),
+            Side::Sell if asset.margin.is_some() => Some((asset.positions + target).max(0.0)),
+            Side::Sell => None,
+        }
+    }
+
+    // Equity positions in the symbol are sized on, with a shared cash pool that is the whole portfolio
+    fn sizing_equity(&self, symbol: &str, price: f64) -> f64 {
+        let Some(asset) = self.assets_data.get(symbol) else { return 0.0 };
+        if self.shared_cash {
+            self.assets_data.values().filter(|other| other.symbol != symbol).map(|other| other.total_value).sum::<f64>()
+                + asset.equity(price)
+        } else {
+            asset.equity(price)
+        }
+    }
+
+    // Cash the symbol can spend, the whole pool when cash is shared, where its own slice may go negative
+    fn available_cash(&self, symbol: &str) -> f64 {
+        if self.shared_cash {
+            self.assets_data.values().map(|asset| asset.cash).sum()
+        } else {
+            self.assets_data.get(symbol).map_or(0.0, |asset| asset.cash)
+        }
+    }
 
     // Takes &mut self since this will modify the Backtrader instance by executing a trade
+    // Returns the fill when the order was (partially) filled
     #[inline(always)]
-    fn execute_trade(&mut self, symbol: &str, signal: i32, price: f64) {
+    fn execute_trade(&mut self, order_id: OrderId, order: &Order, price: f64, candle: &Candle) -> Option<Fill> {
+        let cash = self.available_cash(&order.symbol);
         // Retrieve the asset data for the symbol
-        if let Some(asset) = self.assets_data.get_mut(symbol) {
-            {
-                if signal > 0 && asset.cash > 0.0 { // Buy signal logic
-                    let trade_value = asset.cash;
-                    let commission = self.exchange.calculate_commission(trade_value);
-                    let shares_to_buy = (trade_value - commission) / price;
-                    asset.positions += shares_to_buy;
-                    asset.cash -= trade_value;
-                } else if signal < 0 && asset.positions > 0.0 { // Sell signal logic
-                    let trade_value = asset.positions * price;
-                    let commission = self.exchange.calculate_commission(trade_value);
-                    asset.cash += trade_value - commission;
-                    asset.positions = 0.0;
+        if let Some(asset) = self.assets_data.get_mut(&order.symbol) {
+            // IOC and FOK orders can not take more than the bar traded
+            let volume_cap = match order.time_in_force {
+                TimeInForce::GTC => f64::INFINITY,
+                TimeInForce::IOC | TimeInForce::FOK => candle.volume,
+            };
+
+            let liquidity = order.liquidity(price);
+            let available = asset.max_order_quantity(order.side, price, cash, liquidity, candle.timestamp, &self.exchange);
+            let requested = order.quantity.unwrap_or(available);
+            // A fill or kill order is killed when the bar's volume or the cash can not take all of it
+            let fill_or_kill = order.time_in_force == TimeInForce::FOK;
+            if fill_or_kill && requested > available.min(volume_cap) {
+                return None;
+            }
+            let mut quantity = requested.min(available).min(volume_cap);
+            if quantity <= 0.0 {
+                return None;
+            }
+
+            // Slippage makes buys dearer, so what is available is checked again at the execution price.
+            // A maker rests at its own price and pays none, and no limit order fills beyond its limit
+            let reference_price = price;
+            let price = match liquidity {
+                Liquidity::Maker => reference_price,
+                Liquidity::Taker => self.exchange.execution_price(order.side, reference_price, quantity, candle),
+            };
+            let price = order.within_limit(asset.rules.round_fill_price(order.side, price));
+            if order.side == Side::Buy && price > reference_price {
+                let affordable = asset.max_order_quantity(order.side, price, cash, liquidity, candle.timestamp, &self.exchange);
+                if fill_or_kill && affordable < quantity {
+                    return None;
                 }
+                quantity = quantity.min(affordable);
+            }
 
-                // Update position value and total value
-                //asset.position_value = asset.positions * price;
-                //asset.total_value = asset.cash + asset.position_value;
+            // Sizes are cut down to the lot size, what is then too small for the exchange is rejected
+            quantity = asset.rules.round_quantity(quantity);
+            if let Some(reason) = asset.rules.violation(quantity, price) {
+                self.ledger.record_rejection(Rejection {
+                    order_id,
+                    symbol: order.symbol.clone(),
+                    side: order.side,
+                    timestamp: candle.timestamp,
+                    price,
+                    quantity,
+                    reason,
+                });
+                return None;
+            }
 
-                // Update total value history for tracking
-                //asset.history.push(asset.total_value);
+            let trade_value = quantity * price;
+            let commission = self.exchange.commission(&order.symbol, trade_value, liquidity, candle.timestamp);
+            match order.side {
+                Side::Buy => {
+                    asset.positions += quantity;
+                    asset.cash -= trade_value + commission;
+                }
+                Side::Sell => {
+                    asset.positions -= quantity;
+                    asset.cash += trade_value - commission;
+                }
+            }
+            // Going all in leaves floating point dust, which would otherwise be traded on the next signal
+            if asset.cash.abs() < CASH_DUST {
+                asset.cash = 0.0;
             }
+            if asset.positions.abs() < POSITION_DUST {
+                asset.positions = 0.0;
+            }
+
+            let fill = Fill {
+                order_id,
+                symbol: order.symbol.clone(),
+                side: order.side,
+                timestamp: candle.timestamp,
+                price,
+                quantity,
+                commission,
+                slippage: (price - reference_price).abs() * quantity,
+            };
+            self.record_fill(fill.clone());
+            Some(fill)
         } else {
-            eprintln!("Asset '{}' not found in portfolio.", symbol);
+            eprintln!("Asset '{}' not found in portfolio.", order.symbol);
+            None
+        }
+    }
+
+    // Settle the fundings since the previous bar on the position that was held through them
+    fn settle_funding(&mut self, symbol: &str, price: f64, previous: i64, timestamp: i64) {
+        let Some(asset) = self.assets_data.get_mut(symbol) else {
+            return;
+        };
+        let Some(funding) = &asset.funding else {
+            return;
+        };
+        if asset.positions == 0.0 {
+            return;
+        }
+
+        for &(funding_timestamp, rate) in funding.between(previous, timestamp) {
+            let amount = funding_amount(asset.positions, price, rate);
+            self.funding_payments.push(FundingPayment {
+                symbol: symbol.to_string(),
+                timestamp: funding_timestamp,
+                rate,
+                position: asset.positions,
+                price,
+                amount,
+            });
+            asset.cash += amount;
+            asset.funding_pnl += amount;
+        }
+    }
+
+    // Close the position when the bar reaches one of its exits, the other exits are dropped with it
+    fn check_exits(&mut self, symbol: &str, candle: &Candle) {
+        let Some(rules) = self.exit_rules else { return };
+        let Some(bracket) = self.brackets.get_mut(symbol) else { return };
+        if let Some((order_type, reason)) = bracket.check(&rules, candle) {
+            self.take_exit(symbol, order_type, reason, candle);
         }
     }
 
+    fn take_exit(&mut self, symbol: &str, order_type: OrderType, reason: ExitReason, candle: &Candle) {
+        let Some(bracket) = self.brackets.remove(symbol) else { return };
+        let side = bracket.side.opposite();
+
+        let position = self.assets_data.get(symbol).map_or(0.0, |asset| asset.positions);
+        let mut order = Order { order_type, quantity: Some(position.abs()), ..Order::market(symbol, side) };
+        let Some(price) = order.fill_price(candle) else { return };
+        if let Some(fill) = self.execute_trade(EXIT_ORDER_ID, &order, price, candle) {
+            self.exits.push(Exit {
+                symbol: symbol.to_string(),
+                timestamp: candle.timestamp,
+                price: fill.price,
+                quantity: -side.direction() * fill.quantity,
+                reason,
+            });
+        }
+    }
+
+    // Keep the symbol's bracket in line with its position after the bar's fills.
+    // A new or flipped position gets fresh levels, adding to one moves them to the new average entry.
+    // Levels placed on this bar are checked against it too, its fills came before the rest of its range
+    fn attach_bracket(&mut self, symbol: &str, candle: &Candle, atr: Option<f64>) {
+        let Some(rules) = self.exit_rules else { return };
+        let position = self.assets_data.get(symbol).map_or(0.0, |asset| asset.positions);
+        let Some(trade) = self.ledger.open_trade(symbol).filter(|_| position != 0.0) else {
+            self.brackets.remove(symbol);
+            return;
+        };
+
+        let side = if position > 0.0 { Side::Buy } else { Side::Sell };
+        let mut bracket = match self.brackets.get(symbol) {
+            Some(bracket) if bracket.side == side && bracket.entry_price == trade.entry_price => return,
+            Some(bracket) if bracket.side == side => Bracket {
+                best_price: bracket.best_price,
+                bars_held: bracket.bars_held,
+                ..Bracket::new(&rules, side, trade.entry_price, atr)
+            },
+            _ => Bracket::new(&rules, side, trade.entry_price, atr),
+        };
+        let exit = bracket.check_entry_bar(&rules, candle);
+        self.brackets.insert(symbol.to_string(), bracket);
+        if let Some((order_type, reason)) = exit {
+            self.take_exit(symbol, order_type, reason, candle);
+        }
+    }
+
+    // ATR of the bars before `candle`, what a position opened on it places its exits with, then count the candle in.
+    // Vectorized backtests read it from the `exit_atr` column instead
+    fn next_exit_atr(&mut self, symbol: &str, candle: &Candle) -> Option<f64> {
+        let window = self.exit_rules.and_then(|rules| rules.atr_window())?;
+        let atr = self.exit_atrs.entry(symbol.to_string()).or_insert_with(|| RollingAtr::new(window));
+        let value = atr.value();
+        atr.update(candle);
+        value
+    }
+
+    // Charge borrow fees for the time since the previous bar on shorts and leveraged longs
+    fn accrue_borrow_fees(&mut self, symbol: &str, price: f64, elapsed_ms: i64) {
+        let cash = self.available_cash(symbol);
+        if let Some(asset) = self.assets_data.get_mut(symbol) {
+            if let Some(margin) = asset.margin {
+                let fee = margin.borrow_fee(cash, asset.positions, price, elapsed_ms);
+                asset.cash -= fee;
+                asset.borrow_fees += fee;
+            }
+        }
+    }
+
+    // Close the position when the bar trades through its maintenance margin, pending orders are cancelled with it
+    fn check_liquidation(&mut self, symbol: &str, candle: &Candle) {
+        let cash = self.available_cash(symbol);
+        let Some(asset) = self.assets_data.get_mut(symbol) else { return };
+        let Some(liquidation_price) = asset.margin.and_then(|margin| margin.liquidation_price(cash, asset.positions)) else {
+            return;
+        };
+
+        // Gapping through the liquidation price closes at the open
+        let price = if asset.positions > 0.0 && candle.low <= liquidation_price {
+            candle.open.min(liquidation_price)
+        } else if asset.positions < 0.0 && candle.high >= liquidation_price {
+            candle.open.max(liquidation_price)
+        } else {
+            return;
+        };
+
+        let quantity = asset.positions;
+        let trade_value = quantity.abs() * price;
+        let commission = self.exchange.commission(symbol, trade_value, Liquidity::Taker, candle.timestamp);
+        asset.cash += quantity * price - commission;
+        asset.positions = 0.0;
+        let equity = asset.cash;
+
+        self.order_book.cancel_all(symbol);
+        self.record_fill(Fill {
+            order_id: LIQUIDATION_ORDER_ID,
+            symbol: symbol.to_string(),
+            side: if quantity > 0.0 { Side::Sell } else { Side::Buy },
+            timestamp: candle.timestamp,
+            price,
+            quantity: quantity.abs(),
+            commission,
+            slippage: 0.0,
+        });
+        self.liquidations.push(Liquidation {
+            symbol: symbol.to_string(),
+            timestamp: candle.timestamp,
+            price,
+            quantity,
+            equity,
+        });
+    }
+
     // Takes &mut self since it likely updates the portfolio
     fn update_portfolio(&mut self, symbol: &str, price: f64) {
         // Retrieve the asset data for the symbol
@@ -93,115 +623,571 @@ impl Backtrader {
 
             // Add the updated total value to history for the specific asset
             asset.history.push(asset.total_value);
-
-            // Update the overall portfolio's daily value (optional)
-            if let Some(last_value) = self.daily_portfolio_values.get_mut(symbol).unwrap().last_mut() {
-                *last_value += asset.total_value;
-            } else {
-                self.daily_portfolio_values.get_mut(symbol).unwrap().push(asset.total_value);
+            if asset.positions != 0.0 {
+                asset.exposed_bars += 1;
             }
         } else {
             eprintln!("Asset '{}' not found in portfolio, cannot update portfolio.", symbol);
         }
     }
 
-    // Takes &mut self since it likely modifies or interacts with the Backtrader instance during the backtest process
-    pub fn backtest(&mut self, symbol: Option<String>, strategy: impl StrategyTrait) -> Result<(), PolarsError> {
-        // Split initial capital equally among all assets in data
-        let assets_count = self.assets_data.len() as f64;
+    // Signal frame with the columns the backtest loop reads, plus the ATR when the position sizer asks for it
+    fn prepare_signals(&self, data: &Option<DataFrame>, strategy: &impl StrategyTrait) -> PolarsResult<DataFrame> {
+        let signals = normalize_signals(strategy.generate_signals(&mut &data.clone())?)?;
+        let mut final_signals = signals.lazy();
+        if let Some(window) = self.position_sizer.as_ref().and_then(|sizer| sizer.atr_window()) {
+            final_signals = final_signals.with_column(average_true_range(window).alias("atr"));
+        }
+        if let Some(window) = self.exit_rules.and_then(|rules| rules.atr_window()) {
+            final_signals = final_signals.with_column(average_true_range(window).alias("exit_atr"));
+        }
+        final_signals.collect()
+    }
 
-        let symbols = if symbol.is_some() {
-            vec![symbol.unwrap()]
-        } else {
-            self.assets_data.keys().cloned().collect()
+    // Accrue borrow fees since the previous bar, take exits, fill resting orders, check the margin and bracket new positions, in that order.
+    // `exit_atr` is the ATR the exits of a position opened on this bar are placed with.
+    fn process_fills(&mut self, symbol: &str, candle: &Candle, previous_timestamp: Option<i64>, exit_atr: Option<f64>) {
+        // Positions held since the previous bar pay for what they borrowed and settle funding at this bar's open
+        if let Some(previous) = previous_timestamp {
+            self.accrue_borrow_fees(symbol, candle.open, candle.timestamp - previous);
+            self.settle_funding(symbol, candle.open, previous, candle.timestamp);
+        }
+
+        // Orders placed on earlier bars are filled first, a signal can only trade from the next bar on
+        // While the exchange is closed they keep resting
+        if self.exchange.trading_hours.is_open(candle.timestamp) {
+            self.check_exits(symbol, candle);
+            for (pending, fill_price) in self.order_book.match_candle(symbol, candle) {
+                self.execute_trade(pending.id, &pending.order, fill_price, candle);
+            }
+        }
+        self.check_liquidation(symbol, candle);
+        self.attach_bracket(symbol, candle, exit_atr);
+    }
+
+    // Turn a change of the bar's signal into an order, the strategy's order is cancel/replaced by each new one instead of stacking up.
+    // Events trade when one appears, states trade towards the new target position, going flat closes the position.
+    // A target exposure trades the difference between the position and that fraction of the equity.
+    fn process_signal(
+        &mut self,
+        symbol: &str,
+        columns: &BarColumns,
+        i: usize,
+        candle: &Candle,
+        state: &mut SignalState,
+    ) -> PolarsResult<()> {
+        let position = self.assets_data.get(symbol).map_or(0.0, |asset| asset.positions);
+        if let Some(exposure) = columns.exposure {
+            let Some(target) = exposure.get(i) else { return Ok(()) };
+            if target == std::mem::replace(&mut state.exposure, target) {
+                return Ok(());
+            }
+            let quantity = target * self.sizing_equity(symbol, candle.close) / candle.close - position;
+            // At the new target already, what still rests was placed for the previous one
+            if quantity == 0.0 {
+                self.cancel_signal_order(state);
+                return Ok(());
+            }
+            let side = if quantity > 0.0 { Side::Buy } else { Side::Sell };
+            let order = Order { quantity: Some(quantity.abs()), ..columns.orders.order_at(i, symbol, side)? };
+            self.place_signal_order(order, candle.timestamp, state);
+            return Ok(());
+        }
+
+        let Some(signal) = columns.signals.get(i) else { return Ok(()) };
+        let previous = std::mem::replace(&mut state.last, signal);
+        if signal == previous {
+            return Ok(());
+        }
+
+        let atr = columns.atr.and_then(|atr| atr.get(i));
+        let (side, quantity) = match (columns.signal_kind, signal) {
+            (SignalKind::Event, 0) => return Ok(()),
+            // Nothing long to exit, an entry still resting must not open the position after the exit
+            (SignalKind::Event, -1) if position <= 0.0 => {
+                self.cancel_signal_order(state);
+                return Ok(());
+            }
+            (SignalKind::Event, -1) => (Side::Sell, Some(position)),
+            // Already flat, an entry still resting from the previous target must not open a position later
+            (SignalKind::State, 0) if position == 0.0 => {
+                self.cancel_signal_order(state);
+                return Ok(());
+            }
+            (SignalKind::State, 0) => (if position > 0.0 { Side::Sell } else { Side::Buy }, Some(position.abs())),
+            (_, signal) => {
+                let side = if signal > 0 { Side::Buy } else { Side::Sell };
+                (side, self.order_quantity(symbol, side, candle.close, atr))
+            }
+        };
+        let order = Order { quantity, ..columns.orders.order_at(i, symbol, side)? };
+        self.place_signal_order(order, candle.timestamp, state);
+        Ok(())
+    }
+
+    // Drop the strategy's order still resting from an earlier signal
+    fn cancel_signal_order(&mut self, state: &mut SignalState) {
+        if let Some(id) = state.order.take() {
+            self.order_book.cancel(id);
+        }
+    }
+
+    // Submit the strategy's order for the bar, or replace the one still resting from an earlier signal
+    fn place_signal_order(&mut self, mut order: Order, timestamp: i64, state: &mut SignalState) {
+        if let Some(asset) = self.assets_data.get(&order.symbol) {
+            asset.rules.round_prices(&mut order);
+        }
+        state.order = match state.order {
+            Some(id) if self.order_book.is_pending(id) => self.order_book.replace(id, order, timestamp),
+            _ => Some(self.order_book.submit(order, timestamp)),
+        };
+    }
+
+    // Every backtest starts over from the initial capital, a later run would otherwise continue the earlier one's
+    // cash, positions, orders, fills, ledger and equity curve. Orders submitted by hand before the first run are kept.
+    fn start_run(&mut self) {
+        if self.portfolio_history.values().all(|history| history.is_empty()) {
+            return;
+        }
+
+        let symbol_capital = self.initial_capital / self.assets_data.len() as f64;
+        for asset in self.assets_data.values_mut() {
+            asset.reset(symbol_capital);
+        }
+        for history in self.portfolio_history.values_mut() {
+            history.clear();
+        }
+        self.order_book = OrderBook::new();
+        self.ledger = TradeLedger::new();
+        self.exchange.volume = VolumeTracker::default();
+        self.liquidations.clear();
+        self.funding_payments.clear();
+        self.brackets.clear();
+        self.exits.clear();
+        self.exit_atrs.clear();
+    }
+
+    // Mark the symbol at `price` and record its equity for the bar
+    fn record_bar(&mut self, symbol: &str, timestamp: i64, price: f64) {
+        self.update_portfolio(symbol, price);
+
+        let new_value = self.assets_data.get(symbol).unwrap().total_value;
+        self.portfolio_history.get_mut(symbol).unwrap().push((timestamp, new_value));
+    }
+
+    // Takes &mut self since it likely modifies or interacts with the Backtrader instance during the backtest process
+    // Only the bars within `range` are traded, `DateRange::default()` runs on all of them
+    pub fn backtest(&mut self, symbol: Option<String>, strategy: impl StrategyTrait, range: DateRange) -> Result<(), PolarsError> {
+        let symbols = match symbol {
+            Some(symbol) => vec![symbol],
+            None => self.assets_data.keys().cloned().collect(),
         };
+        self.start_run();
 
-        for symbol in symbols.clone() {
-            let asset = self.assets_data.get_mut(&symbol).unwrap();
-            asset.load_data();
+        for symbol in symbols {
+            let data = self.run_data(&symbol, range)?;
+
+            let final_signals = self.prepare_signals(&Some(data), &strategy)?;
+            let columns = BarColumns::new(&final_signals, strategy.signal_kind())?;
+
+            let mut signal_state = SignalState::default();
+            let mut previous_timestamp: Option<i64> = None;
 
-            let mut signals = strategy.generate_signals(&mut asset.get_data())?;
-            let expr = col("signal").eq(true);
-            let final_signals = signals.lazy().with_columns([expr]).collect()?;
             /* Rather naive, move some of the logic to strategy for flexibility TODO */
             for i in 0..final_signals.height() {
-                let price = final_signals.column("close")?.get(i)?.try_extract::<f64>()?;
-                let signal = final_signals.column("signal")?.get(i)?.try_extract::<i32>()?;
-                /* TODO make consecutive aware so multiple true in a row does not make it fire the entire cash holdings within n consecutive true signals */
-                let timestamp = final_signals.column("timestamp")?.get(i);
-
-                self.execute_trade(&symbol.clone(), signal, price);
-                self.update_portfolio(&symbol.clone(), price);
-
-                let portfolio = self.portfolio_history.get_mut(&symbol.clone()).unwrap();
-                let new_value = self.assets_data.get(&symbol).unwrap().total_value;
-                if portfolio.is_empty() || portfolio.last().unwrap() != &new_value {
-                    /* TODO perhaps store with timestamp? */
-                    portfolio.push(new_value);
-                }
+                let Some(candle) = columns.candles.get(i) else { continue };
+
+                self.process_fills(&symbol, &candle, previous_timestamp, columns.exit_atr_before(i));
+                previous_timestamp = Some(candle.timestamp);
+                self.process_signal(&symbol, &columns, i, &candle, &mut signal_state)?;
+                self.record_bar(&symbol, candle.timestamp, candle.close);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Call back into an event driven strategy with the symbol's current state
+    fn with_context<R>(&mut self, symbol: &str, timestamp: i64, price: f64, callback: impl FnOnce(&mut StrategyContext) -> R) -> R {
+        let equity = self.sizing_equity(symbol, price);
+        let mut context = StrategyContext {
+            symbol,
+            timestamp,
+            price,
+            equity,
+            asset: &self.assets_data[symbol],
+            order_book: &mut self.order_book,
+        };
+        callback(&mut context)
+    }
+
+    /// Backtest an event driven strategy bar by bar on each symbol within `range`, the same way `backtest` runs a vectorized one.
+    /// Every bar fills resting orders first, reports the bar's fills and rejections, then calls `on_bar` at the close.
+    pub fn backtest_events(
+        &mut self,
+        symbol: Option<String>,
+        strategy: &mut impl EventStrategy,
+        range: DateRange,
+    ) -> Result<(), PolarsError> {
+        let symbols = match symbol {
+            Some(symbol) => vec![symbol],
+            None => self.assets_data.keys().cloned().collect(),
+        };
+        self.start_run();
+        self.exit_atrs.clear();
+
+        for symbol in symbols {
+            let data = self.run_data(&symbol, range)?;
+            let candles = CandleColumns::new(&data)?;
+            let bars: Vec<Candle> = (0..data.height()).filter_map(|i| candles.get(i)).collect();
+            let (Some(first), Some(last)) = (bars.first(), bars.last()) else { continue };
 
-                let daily = self.daily_portfolio_values.get_mut(&symbol.clone()).unwrap().last_mut().unwrap();
-                *daily += self.assets_data.get(&symbol).unwrap().total_value;
+            self.with_context(&symbol, first.timestamp, first.open, |context| strategy.on_start(context));
 
-                /*let index = self.portfolio_history.get(&symbol.clone()).unwrap().len().checked_sub(1).unwrap();
-                self.portfolio_history.get_mut(&symbol.clone()).unwrap().insert(
-                    index,
-                    self.assets_data.get(&symbol).unwrap().total_value
-                );
-                let last = self.daily_portfolio_values.get_mut(&symbol.clone()).unwrap().last_mut().unwrap();
-                *last += self.assets_data.get_mut(&symbol).unwrap().total_value;*/
+            let mut previous_timestamp: Option<i64> = None;
+            for candle in &bars {
+                self.process_event_bar(&symbol, candle, previous_timestamp, strategy);
+                previous_timestamp = Some(candle.timestamp);
             }
+
+            self.with_context(&symbol, last.timestamp, last.close, |context| strategy.on_finish(context));
         }
 
         Ok(())
     }
 
+    /// Drive an event driven strategy from a stream of the symbol's candles, a replayed file or a live feed.
+    /// Each candle is appended to the asset's data, those within `range` are processed like a bar of `backtest_events`.
+    pub async fn backtest_stream(
+        &mut self,
+        symbol: &str,
+        stream: &mut CandleStream,
+        strategy: &mut impl EventStrategy,
+        range: DateRange,
+    ) -> Result<(), PolarsError> {
+        if !self.assets_data.contains_key(symbol) {
+            return Err(PolarsError::ComputeError(format!("Asset '{}' not found in portfolio.", symbol).into()));
+        }
+        self.start_run();
+        self.exit_atrs.remove(symbol);
 
-    // Takes &self since performance calculation likely doesn't modify the Backtrader instance
-    pub fn calculate_performance(&self, _plot: bool /* TODO implement plotting */ ) -> Result<(), PolarsError> {
-        if self.daily_portfolio_values.is_empty() {
-            println!("No daily portfolio values found nothing to calculate performance over.");
-            return Ok(())
+        let mut previous: Option<Candle> = None;
+        while let Some(candle) = self.assets_data.get_mut(symbol).unwrap().load_latest_candle(stream).await? {
+            if !range.contains(candle.timestamp) {
+                continue;
+            }
+            if previous.is_none() {
+                self.with_context(symbol, candle.timestamp, candle.open, |context| strategy.on_start(context));
+            }
+            self.process_event_bar(symbol, &candle, previous.map(|previous| previous.timestamp), strategy);
+            previous = Some(candle);
         }
-        println!("Calculating performance over {:?}", self.portfolio_history);
-        println!("Calculating daily performance over {:?}", self.daily_portfolio_values);
-        // Add function name for easier debugging
 
+        if let Some(last) = previous {
+            self.with_context(symbol, last.timestamp, last.close, |context| strategy.on_finish(context));
+        }
+        Ok(())
+    }
+
+    // Fill resting orders, report the bar's fills and rejections, call `on_bar` and mark the portfolio
+    fn process_event_bar(&mut self, symbol: &str, candle: &Candle, previous_timestamp: Option<i64>, strategy: &mut impl EventStrategy) {
+        let (fills, rejections) = (self.fills().len(), self.ledger.rejections().len());
+        let exit_atr = self.next_exit_atr(symbol, candle);
+        self.process_fills(symbol, candle, previous_timestamp, exit_atr);
+
+        let fills: Vec<Fill> = self.fills()[fills..].to_vec();
+        let rejections: Vec<Rejection> = self.ledger.rejections()[rejections..].to_vec();
+        self.with_context(symbol, candle.timestamp, candle.close, |context| {
+            fills.iter().for_each(|fill| strategy.on_fill(fill, context));
+            rejections.iter().for_each(|rejection| strategy.on_order_rejected(rejection, context));
+            strategy.on_bar(candle, context);
+        });
+        self.record_bar(symbol, candle.timestamp, candle.close);
+    }
+
+    /// Every symbol's data within `range` on the union of all timestamps, keyed in symbol order.
+    /// Bars a symbol has no data for are null, and every frame carries the forward filled `close_<symbol>`
+    /// of all symbols plus a `symbol` column, so one strategy can compare assets and still tell them apart.
+    pub fn aligned_data(&mut self, range: DateRange) -> PolarsResult<Vec<(String, DataFrame)>> {
+        let mut symbols: Vec<String> = self.assets_data.keys().cloned().collect();
+        symbols.sort();
+
+        let mut clock = BTreeSet::new();
+        let mut frames = HashMap::new();
+        for symbol in &symbols {
+            let frame = self.run_data(symbol, range)?;
+            let timestamps = frame.column("timestamp")?.cast(&DataType::Int64)?;
+            clock.extend(timestamps.i64()?.into_iter().flatten());
+            frames.insert(symbol.clone(), frame);
+        }
+        let clock = df!("timestamp" => clock.into_iter().collect::<Vec<i64>>())?
+            .lazy()
+            .with_column(col("timestamp").cast(DataType::Datetime(TimeUnit::Milliseconds, None)));
+
+        let data = |symbol: &String| frames[symbol].clone().lazy();
+        let mut closes = clock.clone();
+        for symbol in &symbols {
+            let close = format!("close_{}", symbol);
+            closes = closes
+                .left_join(data(symbol).select([col("timestamp"), col("close").alias(&close)]), col("timestamp"), col("timestamp"))
+                .with_column(col(&close).forward_fill(None));
+        }
+
+        symbols
+            .iter()
+            .map(|symbol| {
+                let frame = clock
+                    .clone()
+                    .left_join(data(symbol), col("timestamp"), col("timestamp"))
+                    .left_join(closes.clone(), col("timestamp"), col("timestamp"))
+                    .with_column(lit(symbol.as_str()).alias("symbol"))
+                    .collect()?;
+                Ok((symbol.clone(), frame))
+            })
+            .collect()
+    }
+
+    /// Backtest every symbol within `range` on a shared clock with one cash pool, so the strategy can rotate between assets.
+    /// Each bar first fills orders for all symbols, then places the new signals, then marks the portfolio.
+    /// A symbol without a bar at a timestamp keeps its last close, margin terms still apply per symbol.
+    pub fn backtest_portfolio(&mut self, strategy: impl StrategyTrait, range: DateRange) -> Result<(), PolarsError> {
+        self.start_run();
+        // Only this run sizes from the pool, later backtests go back to each symbol's own cash
+        self.shared_cash = true;
+        let result = self.run_portfolio(strategy, range);
+        self.shared_cash = false;
+        result
+    }
+
+    fn run_portfolio(&mut self, strategy: impl StrategyTrait, range: DateRange) -> Result<(), PolarsError> {
+        let aligned = self.aligned_data(range)?;
+        let Some((_, first)) = aligned.first() else {
+            return Ok(());
+        };
+        let clock: Vec<i64> = first.column("timestamp")?.datetime()?.into_iter().flatten().collect();
+
+        let frames = aligned
+            .iter()
+            .map(|(symbol, data)| Ok((symbol.clone(), self.prepare_signals(&Some(data.clone()), &strategy)?)))
+            .collect::<PolarsResult<Vec<(String, DataFrame)>>>()?;
+        let columns = frames
+            .iter()
+            .map(|(_, frame)| BarColumns::new(frame, strategy.signal_kind()))
+            .collect::<PolarsResult<Vec<BarColumns>>>()?;
+
+        let mut signal_states: Vec<SignalState> = vec![SignalState::default(); frames.len()];
+        let mut previous_timestamps: Vec<Option<i64>> = vec![None; frames.len()];
+        let mut last_closes: Vec<Option<f64>> = vec![None; frames.len()];
+
+        for (i, timestamp) in clock.into_iter().enumerate() {
+            let candles: Vec<Option<Candle>> = columns.iter().map(|columns| columns.candles.get(i)).collect();
 
-        let mut total_portfolio_value = 0.0;
-        /* TODO print both total and for each different symbol */
-        for (_, value) in self.daily_portfolio_values.iter() {
-            total_portfolio_value += value.last().unwrap();
+            for (k, (symbol, _)) in frames.iter().enumerate() {
+                if let Some(candle) = candles[k] {
+                    self.process_fills(symbol, &candle, previous_timestamps[k], columns[k].exit_atr_before(i));
+                    previous_timestamps[k] = Some(candle.timestamp);
+                }
+            }
+
+            for (k, (symbol, _)) in frames.iter().enumerate() {
+                if let Some(candle) = candles[k] {
+                    self.process_signal(symbol, &columns[k], i, &candle, &mut signal_states[k])?;
+                }
+            }
+
+            for (k, (symbol, _)) in frames.iter().enumerate() {
+                if let Some(candle) = candles[k] {
+                    last_closes[k] = Some(candle.close);
+                }
+                // Nothing is recorded before a symbol's first bar, its slice of cash is untouched until then
+                if let Some(close) = last_closes[k] {
+                    self.record_bar(symbol, timestamp, close);
+                }
+            }
         }
 
-        println!("Final Portfolio Value: {:.2}", total_portfolio_value);
+        Ok(())
+    }
+
+
+    /// Target weights of a strategy evaluated on the aligned data, see `aligned_data` for the columns it can use.
+    /// Every `weight_<symbol>` column it produces is kept, next to the timestamp.
+    pub fn target_weights(&mut self, strategy: &impl StrategyTrait, range: DateRange) -> PolarsResult<DataFrame> {
+        let aligned = self.aligned_data(range)?;
+        let Some((_, data)) = aligned.into_iter().next() else {
+            return Err(PolarsError::NoData("No assets to compute target weights for.".into()));
+        };
+        strategy.generate_weights(&mut &Some(data))
+    }
+
+    /// Rebalance a shared cash pool to the `weight_<symbol>` columns of `weights` whenever `schedule` is due within `range`.
+    /// Weights hold from their timestamp until the next row, a symbol without a weight column is not held.
+    /// Rebalancing orders are market orders placed at the bar's close and filled from the next bar, sells first.
+    pub fn backtest_rebalance(&mut self, weights: &DataFrame, schedule: RebalanceSchedule, range: DateRange) -> Result<(), PolarsError> {
+        self.start_run();
+        self.shared_cash = true;
+        let result = self.run_rebalance(weights, schedule, range);
+        self.shared_cash = false;
+        result
+    }
+
+    fn run_rebalance(&mut self, weights: &DataFrame, schedule: RebalanceSchedule, range: DateRange) -> Result<(), PolarsError> {
+        self.exit_atrs.clear();
+        let aligned = self.aligned_data(range)?;
+        let Some((_, first)) = aligned.first() else {
+            return Ok(());
+        };
+        let clock = first.select(["timestamp"])?;
+        let timestamps: Vec<i64> = clock.column("timestamp")?.datetime()?.into_iter().flatten().collect();
+
+        // Weights on the backtest's clock, carried forward between their own timestamps
+        let weight_columns: Vec<String> = aligned.iter().map(|(symbol, _)| format!("{}{}", WEIGHT_PREFIX, symbol)).collect();
+        let schema = weights.schema();
+        let present: Vec<&String> = weight_columns.iter().filter(|name| schema.contains(name)).collect();
+        let targets = clock
+            .lazy()
+            .with_column(col("timestamp").cast(DataType::Int64))
+            .join_builder()
+            .with(weights.clone().lazy().with_column(col("timestamp").cast(DataType::Int64)))
+            .on([col("timestamp")])
+            .how(JoinType::Left)
+            .finish()
+            .with_columns(present.iter().map(|name| col(name.as_str()).cast(DataType::Float64).forward_fill(None)).collect::<Vec<_>>())
+            .collect()?;
+        let target_values = weight_columns
+            .iter()
+            .map(|name| if schema.contains(name) { Ok(Some(targets.column(name)?.f64()?)) } else { Ok(None) })
+            .collect::<PolarsResult<Vec<Option<&Float64Chunked>>>>()?;
+
+        let columns = aligned
+            .iter()
+            .map(|(_, frame)| CandleColumns::new(frame))
+            .collect::<PolarsResult<Vec<CandleColumns>>>()?;
+
+        let mut rebalance_orders: Vec<Option<OrderId>> = vec![None; aligned.len()];
+        let mut previous_timestamps: Vec<Option<i64>> = vec![None; aligned.len()];
+        let mut last_closes: Vec<Option<f64>> = vec![None; aligned.len()];
+        let mut last_rebalance: Option<i64> = None;
+
+        for (i, timestamp) in timestamps.into_iter().enumerate() {
+            let candles: Vec<Option<Candle>> = columns.iter().map(|columns| columns.get(i)).collect();
+
+            // Symbols with a pending sell fill first, so their proceeds can pay for the buys
+            let mut fill_order: Vec<usize> = (0..aligned.len()).collect();
+            fill_order.sort_by_key(|&k| !self.order_book.pending(&aligned[k].0).iter().any(|pending| pending.order.side == Side::Sell));
+            for k in fill_order {
+                if let Some(candle) = candles[k] {
+                    let exit_atr = self.next_exit_atr(&aligned[k].0, &candle);
+                    self.process_fills(&aligned[k].0, &candle, previous_timestamps[k], exit_atr);
+                    previous_timestamps[k] = Some(candle.timestamp);
+                    last_closes[k] = Some(candle.close);
+                }
+            }
+
+            let holdings: Vec<(f64, f64)> = aligned
+                .iter()
+                .zip(&last_closes)
+                .map(|((symbol, _), close)| (self.assets_data[symbol].positions, close.unwrap_or(0.0)))
+                .collect();
+            let equity: f64 = aligned
+                .iter()
+                .zip(&holdings)
+                .map(|((symbol, _), &(_, price))| self.assets_data[symbol].equity(price))
+                .sum();
+            // Nothing is rebalanced before the first row of weights
+            let weights_in_effect = target_values.iter().flatten().any(|values| values.get(i).is_some());
+            let target_weights: Vec<Option<f64>> = target_values
+                .iter()
+                .zip(&last_closes)
+                .map(|(values, close)| match (values, close) {
+                    (_, None) => None, // Not tradable before its first bar
+                    (Some(values), Some(_)) => Some(values.get(i).unwrap_or(0.0)),
+                    (None, Some(_)) => Some(0.0),
+                })
+                .collect();
 
-        if self.daily_portfolio_values.len() > 0 {
-            for (symbol, value) in self.daily_portfolio_values.iter() {
-                println!("Final value for pair {}: {:.2}", symbol, value.last().unwrap());
+            let drift = weight_drift(&holdings, &target_weights, equity);
+            if weights_in_effect && schedule.is_due(last_rebalance, timestamp, drift) {
+                let quantities = rebalance_quantities(&holdings, &target_weights, equity);
+                for (k, quantity) in quantities.into_iter().enumerate() {
+                    let symbol = &aligned[k].0;
+                    if let Some(id) = rebalance_orders[k].take() {
+                        self.order_book.cancel(id);
+                    }
+                    if quantity.abs() < POSITION_DUST {
+                        continue;
+                    }
+                    let side = if quantity > 0.0 { Side::Buy } else { Side::Sell };
+                    let order = Order { quantity: Some(quantity.abs()), ..Order::market(symbol, side) };
+                    rebalance_orders[k] = Some(self.order_book.submit(order, timestamp));
+                }
+                last_rebalance = Some(timestamp);
+            }
+
+            for (k, (symbol, _)) in aligned.iter().enumerate() {
+                if let Some(close) = last_closes[k] {
+                    self.record_bar(symbol, timestamp, close);
+                }
             }
         }
 
-        let total_return = calculate_total_return(total_portfolio_value, self.initial_capital);
-        println!("Total Return: {:.2}%", total_return * 100.0);
+        Ok(())
+    }
 
-        /* TODO naive approach, use a ticker implementation to get the yearly yield */
+    // Run `strategy`'s target weights through `backtest_rebalance`
+    pub fn backtest_weights(&mut self, strategy: impl StrategyTrait, schedule: RebalanceSchedule, range: DateRange) -> Result<(), PolarsError> {
+        let weights = self.target_weights(&strategy, range)?;
+        self.backtest_rebalance(&weights, schedule, range)
+    }
 
-        let annualized_return = calculate_annualized_yearly_return(total_return, self.daily_portfolio_values.len() as u32);
-        println!("Annualized Return: {:.2}%", annualized_return * 100.0);
 
-        let daily_returns = calculate_daily_returns(self.daily_portfolio_values.get("BTCUSDT").unwrap());
-        println!("Daily Returns: {:?}", daily_returns);
-        //let annualized_volatility = calculate_annualized_volatility(daily_returns);
-        //println!("Annualized Volatility: {:.2}%", annualized_volatility * 100.0);
-        //println!("Sharpe Ratio: {sharpe_ratio:.2f}");
-        //println!("Sortino Ratio: {sortino_ratio:.2f}");
-        //println!("Maximum Drawdown: {max_drawdown * 100:.2f}%");
+    // Takes &self since performance calculation likely doesn't modify the Backtrader instance
+    pub fn calculate_performance(&self, _plot: bool /* TODO implement plotting */ ) -> Result<PerformanceReport, PolarsError> {
+        if self.portfolio_history.values().all(|history| history.is_empty()) {
+            return Err(PolarsError::NoData("No portfolio history found, run a backtest before calculating performance.".into()));
+        }
+
+        // Intraday curves are annualized on daily closes, periods per year follow the detected bar frequency
+        let symbol_capital = self.initial_capital / self.assets_data.len() as f64;
+
+        let mut symbols = HashMap::new();
+        let mut curves = vec![];
+        let mut exposed_bars = 0;
+        let mut bars = 0;
+        for (symbol, history) in self.portfolio_history.iter() {
+            let asset = self.assets_data.get(symbol).unwrap();
+            let trades: Vec<&Trade> = self.ledger.trades().iter().filter(|trade| &trade.symbol == symbol).collect();
+            let exposure_time = asset.exposed_bars as f64 / asset.history.len().max(1) as f64;
+            let (curve, periods_per_year) = annualization_curve(history);
+            let mut metrics =
+                PerformanceMetrics::calculate(&curve, symbol_capital, &trades, exposure_time, periods_per_year, self.risk_free_rate)?;
+            if let Some(benchmark) = self.benchmark_equity(Some(symbol))? {
+                metrics.benchmark = Some(BenchmarkMetrics::calculate(&curve, &benchmark, periods_per_year, self.risk_free_rate)?);
+            }
+            symbols.insert(symbol.clone(), metrics);
+
+            curves.push((history.as_slice(), symbol_capital));
+            exposed_bars += asset.exposed_bars;
+            bars += asset.history.len();
+        }
 
-        /* TODO remove when all metrics works! */
-        unimplemented!("Function 'calculate_performance' is not implemented yet.");
+        let trades: Vec<&Trade> = self.ledger.trades().iter().collect();
+        let equity = aggregate_equity(&curves);
+        let frequency = BarFrequency::detect(&equity.iter().map(|point| point.0).collect::<Vec<_>>());
+        let (curve, periods_per_year) = annualization_curve(&equity);
+        let mut aggregate = PerformanceMetrics::calculate(
+            &curve,
+            self.initial_capital,
+            &trades,
+            exposed_bars as f64 / bars.max(1) as f64,
+            periods_per_year,
+            self.risk_free_rate,
+        )?;
+        if let Some(benchmark) = self.benchmark_equity(None)? {
+            aggregate.benchmark = Some(BenchmarkMetrics::calculate(&curve, &benchmark, periods_per_year, self.risk_free_rate)?);
+        }
 
-        //Ok(())
+        Ok(PerformanceReport { frequency, aggregate, symbols })
     }
 
     // Takes &self since plotting performance is a read-only operation
@@ -1,14 +1,53 @@
+use crate::backtrader::exchange_profile::TradingHours;
+use crate::backtrader::fees::{FeeSchedule, VolumeTracker};
+use crate::backtrader::order::{Liquidity, Side};
+use crate::backtrader::slippage::SlippageModel;
+use crate::data::candle::Candle;
+
 #[derive(Debug)]
 pub struct Exchange {
     pub name: String,
     pub commission_pct: f64,
     pub commission_fixed: f64,
+    pub slippage: Option<Box<dyn SlippageModel>>, // Fills at the order's price without one
+    pub fees: Option<FeeSchedule>, // Replaces the flat commission with maker/taker tiers
+    pub volume: VolumeTracker,     // Trailing volume that places the account in a fee tier
+    pub trading_hours: TradingHours, // Resting orders only fill while the exchange is open
 }
 
 impl Exchange {
     // Takes &self since it doesn't modify the Backtrader instance
-    pub fn calculate_commission(&mut self, trade_value: f64) -> f64 {
+    pub fn calculate_commission(&self, trade_value: f64) -> f64 {
         // Add function name for easier debugging
         (trade_value * self.commission_pct).max(self.commission_fixed)
     }
-}
\ No newline at end of file
+
+    // Commission of a fill, with a fee schedule the fill also counts towards the volume tiers
+    pub fn commission(&mut self, symbol: &str, trade_value: f64, liquidity: Liquidity, timestamp: i64) -> f64 {
+        let Some(fees) = &self.fees else {
+            return self.calculate_commission(trade_value);
+        };
+        let trailing_volume = self.volume.trailing_volume(timestamp);
+        self.volume.record(timestamp, trade_value);
+        fees.fee(symbol, liquidity, trade_value, trailing_volume)
+    }
+
+    // Largest trade value where trade value plus its commission still fits in the cash, at the rate of the tier the fill falls in
+    pub fn max_trade_value(&self, symbol: &str, liquidity: Liquidity, timestamp: i64, cash: f64) -> f64 {
+        let (commission_pct, commission_fixed) = match &self.fees {
+            Some(fees) => (fees.rate(symbol, liquidity, self.volume.volume_at(timestamp)), fees.minimum_fee),
+            None => (self.commission_pct, self.commission_fixed),
+        };
+        (cash / (1.0 + commission_pct))
+            .min(cash - commission_fixed)
+            .max(0.0)
+    }
+
+    // Price a fill of `quantity` at `price` executes at after slippage
+    pub fn execution_price(&self, side: Side, price: f64, quantity: f64, candle: &Candle) -> f64 {
+        match &self.slippage {
+            Some(model) => model.execution_price(side, price, quantity, candle),
+            None => price,
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use crate::backtrader::exchange::Exchange;
+use crate::backtrader::fees::{FeeSchedule, VolumeTracker};
+use crate::backtrader::order::{Order, OrderType, Side};
+use crate::performance::frequency::{weekday, MS_PER_DAY};
+
+// Name of the exchange `Backtrader::new` trades on, it accepts data recorded on any exchange
+pub const CUSTOM_EXCHANGE: &str = "Custom";
+
+// Order constraints of a market, sizes are in base units and prices in quote currency
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SymbolRules {
+    pub tick_size: f64, // Prices are multiples of it
+    pub lot_size: f64,  // Quantities are multiples of it
+    pub min_quantity: f64,
+    pub min_notional: f64, // Smallest quantity * price the exchange accepts
+}
+
+type Rounding = fn(f64) -> f64;
+
+impl SymbolRules {
+    // No constraints, what a symbol without rules trades under
+    pub const UNCONSTRAINED: SymbolRules = SymbolRules { tick_size: 0.0, lot_size: 0.0, min_quantity: 0.0, min_notional: 0.0 };
+
+    // Quantities are rounded down so an order never trades more than it could pay for
+    pub fn round_quantity(&self, quantity: f64) -> f64 {
+        round_to(quantity, self.lot_size, f64::floor)
+    }
+
+    /// Move limit and stop prices onto the tick grid, always to the side that is less aggressive:
+    /// buy limits and sell stops round down, sell limits and buy stops round up.
+    pub fn round_prices(&self, order: &mut Order) {
+        let (limit, stop): (Rounding, Rounding) = match order.side {
+            Side::Buy => (f64::floor, f64::ceil),
+            Side::Sell => (f64::ceil, f64::floor),
+        };
+        order.order_type = match order.order_type {
+            OrderType::Market => OrderType::Market,
+            OrderType::Limit { limit_price } => OrderType::Limit { limit_price: round_to(limit_price, self.tick_size, limit) },
+            OrderType::Stop { stop_price } => OrderType::Stop { stop_price: round_to(stop_price, self.tick_size, stop) },
+            OrderType::StopLimit { stop_price, limit_price } => OrderType::StopLimit {
+                stop_price: round_to(stop_price, self.tick_size, stop),
+                limit_price: round_to(limit_price, self.tick_size, limit),
+            },
+        };
+    }
+
+    // Execution prices land on the tick the exchange would match at, against the trader
+    pub fn round_fill_price(&self, side: Side, price: f64) -> f64 {
+        match side {
+            Side::Buy => round_to(price, self.tick_size, f64::ceil),
+            Side::Sell => round_to(price, self.tick_size, f64::floor),
+        }
+    }
+
+    // Why a fill of `quantity` at `price` is not accepted, None when it is
+    pub fn violation(&self, quantity: f64, price: f64) -> Option<String> {
+        if quantity <= 0.0 || quantity < self.min_quantity {
+            Some(format!("quantity {} below the minimum of {}", quantity, self.min_quantity))
+        } else if quantity * price < self.min_notional {
+            Some(format!("notional {:.2} below the minimum of {}", quantity * price, self.min_notional))
+        } else {
+            None
+        }
+    }
+}
+
+fn round_to(value: f64, step: f64, rounding: fn(f64) -> f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    // The epsilon keeps values already on the grid from being pushed a step by floating point error
+    let steps = value / step;
+    let nearest = steps.round();
+    if (steps - nearest).abs() < 1e-9 {
+        nearest * step
+    } else {
+        rounding(steps) * step
+    }
+}
+
+// When the exchange matches orders, in UTC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TradingHours {
+    Continuous,
+    Weekdays { open_ms: i64, close_ms: i64 }, // Monday to Friday between these offsets from midnight
+}
+
+impl TradingHours {
+    pub const fn is_open(&self, timestamp: i64) -> bool {
+        match *self {
+            TradingHours::Continuous => true,
+            TradingHours::Weekdays { open_ms, close_ms } => {
+                let time = timestamp.rem_euclid(MS_PER_DAY);
+                weekday(timestamp) < 5 && time >= open_ms && time < close_ms
+            }
+        }
+    }
+}
+
+/// Fees, order constraints and hours of an exchange.
+/// The built in tables follow what the exchanges published at the time of writing, check them before relying on them.
+/// Their fees come from `examples/config/fees.json`, the one place the tier tables are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeProfile {
+    pub name: String,
+    pub fees: FeeSchedule,
+    pub trading_hours: TradingHours,
+    pub symbols: HashMap<String, SymbolRules>, // Supported symbols, an empty map accepts any symbol unconstrained
+}
+
+impl ExchangeProfile {
+    pub fn binance() -> Self {
+        ExchangeProfile {
+            name: "Binance".to_string(),
+            fees: FeeSchedule::built_in("binance"),
+            trading_hours: TradingHours::Continuous,
+            symbols: HashMap::from([
+                rules("BTCUSDT", 0.01, 0.00001, 0.00001, 5.0),
+                rules("ETHUSDT", 0.01, 0.0001, 0.0001, 5.0),
+                rules("SOLUSDT", 0.01, 0.001, 0.001, 5.0),
+            ]),
+        }
+    }
+
+    pub fn kraken() -> Self {
+        ExchangeProfile {
+            name: "Kraken".to_string(),
+            fees: FeeSchedule::built_in("kraken"),
+            trading_hours: TradingHours::Continuous,
+            symbols: HashMap::from([
+                rules("XBTUSD", 0.1, 0.00000001, 0.0001, 0.5),
+                rules("ETHUSD", 0.01, 0.00000001, 0.002, 0.5),
+            ]),
+        }
+    }
+
+    pub fn coinbase() -> Self {
+        ExchangeProfile {
+            name: "Coinbase".to_string(),
+            fees: FeeSchedule::built_in("coinbase"),
+            trading_hours: TradingHours::Continuous,
+            symbols: HashMap::from([
+                rules("BTC-USD", 0.01, 0.00000001, 0.00000001, 1.0),
+                rules("ETH-USD", 0.01, 0.00000001, 0.00000001, 1.0),
+            ]),
+        }
+    }
+
+    pub fn bybit() -> Self {
+        ExchangeProfile {
+            name: "Bybit".to_string(),
+            fees: FeeSchedule::built_in("bybit"),
+            trading_hours: TradingHours::Continuous,
+            symbols: HashMap::from([
+                rules("BTCUSDT", 0.01, 0.000001, 0.000048, 1.0),
+                rules("ETHUSDT", 0.01, 0.00001, 0.00062, 1.0),
+            ]),
+        }
+    }
+
+    // Flat commission, any symbol and no constraints, what `Backtrader::new` trades on as `CUSTOM_EXCHANGE`
+    pub fn custom(name: &str, commission_pct: f64, commission_fixed: f64) -> Self {
+        ExchangeProfile {
+            name: name.to_string(),
+            fees: FeeSchedule::flat(commission_pct, commission_fixed),
+            trading_hours: TradingHours::Continuous,
+            symbols: HashMap::new(),
+        }
+    }
+
+    // Built in profile by case insensitive name
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "binance" => Some(Self::binance()),
+            "kraken" => Some(Self::kraken()),
+            "coinbase" => Some(Self::coinbase()),
+            "bybit" => Some(Self::bybit()),
+            _ => None,
+        }
+    }
+
+    pub fn supports(&self, symbol: &str) -> bool {
+        self.symbols.is_empty() || self.symbols.contains_key(symbol)
+    }
+
+    // Instrument metadata of a symbol, unlisted symbols are not constrained
+    pub fn rules(&self, symbol: &str) -> SymbolRules {
+        self.symbols.get(symbol).copied().unwrap_or(SymbolRules::UNCONSTRAINED)
+    }
+
+    pub fn exchange(&self) -> Exchange {
+        let flat = self.fees.tiers.len() == 1
+            && self.fees.symbols.is_empty()
+            && self.fees.tiers[0].maker == self.fees.tiers[0].taker;
+        Exchange {
+            name: self.name.clone(),
+            commission_pct: self.fees.tiers.first().map_or(0.0, |tier| tier.taker),
+            commission_fixed: self.fees.minimum_fee,
+            slippage: None,
+            // A flat schedule is what the plain commission already charges
+            fees: if flat && !self.fees.pay_with_fee_token { None } else { Some(self.fees.clone()) },
+            volume: VolumeTracker::default(),
+            trading_hours: self.trading_hours,
+        }
+    }
+}
+
+fn rules(symbol: &str, tick_size: f64, lot_size: f64, min_quantity: f64, min_notional: f64) -> (String, SymbolRules) {
+    (symbol.to_string(), SymbolRules { tick_size, lot_size, min_quantity, min_notional })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rounding() {
+        let rules = SymbolRules { tick_size: 0.5, lot_size: 0.001, min_quantity: 0.01, min_notional: 10.0 };
+        assert_eq!(rules.round_quantity(1.23456), 1.234);
+        assert_eq!(rules.round_quantity(0.3), 0.3);
+
+        let mut buy = Order { order_type: OrderType::Limit { limit_price: 100.3 }, ..Order::market("BTCUSDT", Side::Buy) };
+        rules.round_prices(&mut buy);
+        assert_eq!(buy.order_type, OrderType::Limit { limit_price: 100.0 });
+
+        let mut sell = Order {
+            order_type: OrderType::StopLimit { stop_price: 99.7, limit_price: 99.2 },
+            ..Order::market("BTCUSDT", Side::Sell)
+        };
+        rules.round_prices(&mut sell);
+        assert_eq!(sell.order_type, OrderType::StopLimit { stop_price: 99.5, limit_price: 99.5 });
+
+        assert_eq!(rules.round_fill_price(Side::Buy, 100.1), 100.5);
+        assert_eq!(rules.round_fill_price(Side::Sell, 100.4), 100.0);
+        assert_eq!(rules.round_fill_price(Side::Buy, 100.5), 100.5);
+    }
+
+    #[test]
+    fn test_violations() {
+        let rules = SymbolRules { tick_size: 0.5, lot_size: 0.001, min_quantity: 0.01, min_notional: 10.0 };
+        assert!(rules.violation(0.005, 10_000.0).is_some());
+        assert!(rules.violation(0.05, 100.0).is_some());
+        assert_eq!(rules.violation(0.2, 100.0), None);
+    }
+
+    #[test]
+    fn test_trading_hours() {
+        let hour = MS_PER_DAY / 24;
+        let hours = TradingHours::Weekdays { open_ms: 9 * hour, close_ms: 17 * hour };
+        // 1970-01-05 was a Monday
+        let monday = 4 * MS_PER_DAY;
+        assert!(hours.is_open(monday + 10 * hour));
+        assert!(!hours.is_open(monday + 18 * hour));
+        assert!(!hours.is_open(monday - MS_PER_DAY + 10 * hour));
+        assert!(TradingHours::Continuous.is_open(monday - MS_PER_DAY));
+    }
+
+    #[test]
+    fn test_registry() {
+        let kraken = ExchangeProfile::by_name("KRAKEN").unwrap();
+        assert!(kraken.supports("XBTUSD"));
+        assert!(!kraken.supports("BTCUSDT"));
+        assert!(ExchangeProfile::custom("Paper", 0.001, 0.0).supports("ANY"));
+        assert_eq!(ExchangeProfile::by_name("Bitstamp"), None);
+        assert!(ExchangeProfile::binance().exchange().fees.is_some());
+    }
+}
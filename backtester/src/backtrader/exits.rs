@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+use crate::backtrader::order::{OrderType, Side};
+use crate::data::candle::Candle;
+
+// Distance of a stop loss or take profit from the entry price
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitLevel {
+    Percent(f64), // Fraction of the entry price, 0.05 is 5%
+    AtrMultiple { multiple: f64, window: usize }, // Multiple of the ATR known when the position was entered
+}
+
+/// Exits attached to every position the engine opens, stop loss and take profit are one-cancels-other.
+/// They are checked against each bar's high and low before the strategy's own signal is acted on,
+/// starting with the rest of the bar the position was entered on.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ExitRules {
+    pub stop_loss: Option<ExitLevel>,
+    pub take_profit: Option<ExitLevel>,
+    pub trailing_stop: Option<f64>, // Fraction the price may retrace from its best level since entry
+    pub max_bars: Option<usize>,    // Close at the open this many bars after the entry bar
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    StopLoss,
+    TakeProfit,
+    TrailingStop,
+    TimeExit,
+}
+
+// A position closed by its exit rules
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exit {
+    pub symbol: String,
+    pub timestamp: i64,
+    pub price: f64,
+    pub quantity: f64, // Signed position that was closed, negative for shorts
+    pub reason: ExitReason,
+}
+
+// Exit levels of one open position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bracket {
+    pub side: Side, // Buy for longs and Sell for shorts
+    pub entry_price: f64,
+    pub stop_loss: Option<f64>,
+    pub take_profit: Option<f64>,
+    pub best_price: f64, // Highest high of a long or lowest low of a short since entry, what the trailing stop follows
+    pub bars_held: usize, // Bars since the one the position was entered on
+}
+
+impl ExitLevel {
+    fn distance(&self, entry_price: f64, atr: Option<f64>) -> Option<f64> {
+        match *self {
+            ExitLevel::Percent(fraction) => Some(entry_price * fraction),
+            ExitLevel::AtrMultiple { multiple, .. } => atr.map(|atr| atr * multiple),
+        }
+    }
+}
+
+impl ExitRules {
+    // Window of the ATR the levels need, the longest one when stop and target both use it
+    pub fn atr_window(&self) -> Option<usize> {
+        [self.stop_loss, self.take_profit]
+            .into_iter()
+            .flatten()
+            .filter_map(|level| match level {
+                ExitLevel::AtrMultiple { window, .. } => Some(window),
+                ExitLevel::Percent(_) => None,
+            })
+            .max()
+    }
+}
+
+impl ExitReason {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            ExitReason::StopLoss => "stop_loss",
+            ExitReason::TakeProfit => "take_profit",
+            ExitReason::TrailingStop => "trailing_stop",
+            ExitReason::TimeExit => "time_exit",
+        }
+    }
+}
+
+impl Bracket {
+    // Levels for a position entered at `entry_price`, ATR levels are left out while the ATR is still warming up
+    pub fn new(rules: &ExitRules, side: Side, entry_price: f64, atr: Option<f64>) -> Self {
+        let direction = side.direction();
+        let level = |level: Option<ExitLevel>, sign: f64| {
+            level
+                .and_then(|level| level.distance(entry_price, atr))
+                .map(|distance| entry_price + sign * direction * distance)
+        };
+        Bracket {
+            side,
+            entry_price,
+            stop_loss: level(rules.stop_loss, -1.0),
+            take_profit: level(rules.take_profit, 1.0),
+            best_price: entry_price,
+            bars_held: 0,
+        }
+    }
+
+    fn trailing_level(&self, rules: &ExitRules) -> Option<f64> {
+        rules.trailing_stop.map(|fraction| self.best_price * (1.0 - self.side.direction() * fraction))
+    }
+
+    /// Exit the candle triggers and the order type it fills as, if any.
+    /// When a bar reaches both the stop and the target, the stop is assumed to have come first.
+    /// The trailing stop follows the best price of earlier bars, this bar's extreme only moves it for the next one.
+    pub fn check(&mut self, rules: &ExitRules, candle: &Candle) -> Option<(OrderType, ExitReason)> {
+        self.bars_held += 1;
+        if rules.max_bars.is_some_and(|max_bars| self.bars_held >= max_bars) {
+            return Some((OrderType::Market, ExitReason::TimeExit));
+        }
+        self.check_levels(rules, candle)
+    }
+
+    /// Stop and target against what the entry bar traded after the position was entered on it.
+    /// The entry bar does not count towards `max_bars`.
+    pub fn check_entry_bar(&mut self, rules: &ExitRules, candle: &Candle) -> Option<(OrderType, ExitReason)> {
+        self.check_levels(rules, candle)
+    }
+
+    fn check_levels(&mut self, rules: &ExitRules, candle: &Candle) -> Option<(OrderType, ExitReason)> {
+        let long = self.side == Side::Buy;
+        let breached = |stop: f64| if long { candle.low <= stop } else { candle.high >= stop };
+        let fixed = self.stop_loss.filter(|&stop| breached(stop));
+        let trailing = self.trailing_level(rules).filter(|&stop| breached(stop));
+        // The tighter of two breached stops is the one the price reached first
+        let stop = match (fixed, trailing) {
+            (Some(fixed), Some(trailing)) if (trailing - fixed) * self.side.direction() > 0.0 => {
+                Some((trailing, ExitReason::TrailingStop))
+            }
+            (Some(fixed), _) => Some((fixed, ExitReason::StopLoss)),
+            (None, Some(trailing)) => Some((trailing, ExitReason::TrailingStop)),
+            (None, None) => None,
+        };
+        if let Some((stop_price, reason)) = stop {
+            return Some((OrderType::Stop { stop_price }, reason));
+        }
+
+        let reached = |target: f64| if long { candle.high >= target } else { candle.low <= target };
+        if let Some(limit_price) = self.take_profit.filter(|&target| reached(target)) {
+            return Some((OrderType::Limit { limit_price }, ExitReason::TakeProfit));
+        }
+
+        self.best_price = if long { self.best_price.max(candle.high) } else { self.best_price.min(candle.low) };
+        None
+    }
+}
+
+// Average true range updated one candle at a time, the same values `average_true_range` gives on a frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollingAtr {
+    window: usize,
+    previous_close: Option<f64>,
+    ranges: VecDeque<f64>,
+}
+
+impl RollingAtr {
+    pub fn new(window: usize) -> Self {
+        RollingAtr { window, previous_close: None, ranges: VecDeque::with_capacity(window) }
+    }
+
+    pub fn update(&mut self, candle: &Candle) {
+        // The first candle has no previous close, its true range is its own range
+        let previous = self.previous_close.unwrap_or(candle.close);
+        let range = (candle.high - candle.low).max(candle.high - previous).max(previous - candle.low);
+        self.ranges.push_back(range);
+        if self.ranges.len() > self.window {
+            self.ranges.pop_front();
+        }
+        self.previous_close = Some(candle.close);
+    }
+
+    // None until `window` candles were seen
+    pub fn value(&self) -> Option<f64> {
+        (self.window > 0 && self.ranges.len() == self.window).then(|| self.ranges.iter().sum::<f64>() / self.window as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::candle::candle;
+
+    #[test]
+    fn test_levels() {
+        let rules = ExitRules {
+            stop_loss: Some(ExitLevel::Percent(0.05)),
+            take_profit: Some(ExitLevel::AtrMultiple { multiple: 2.0, window: 14 }),
+            ..ExitRules::default()
+        };
+        assert_eq!(rules.atr_window(), Some(14));
+
+        let long = Bracket::new(&rules, Side::Buy, 100.0, Some(3.0));
+        assert_eq!(long.stop_loss, Some(95.0));
+        assert_eq!(long.take_profit, Some(106.0));
+
+        let short = Bracket::new(&rules, Side::Sell, 100.0, None);
+        assert_eq!(short.stop_loss, Some(105.0));
+        assert_eq!(short.take_profit, None);
+    }
+
+    #[test]
+    fn test_stop_before_target() {
+        let rules = ExitRules {
+            stop_loss: Some(ExitLevel::Percent(0.05)),
+            take_profit: Some(ExitLevel::Percent(0.05)),
+            ..ExitRules::default()
+        };
+        let mut bracket = Bracket::new(&rules, Side::Buy, 100.0, None);
+        assert_eq!(bracket.check(&rules, &candle(100.0, 104.0, 96.0, 101.0)), None);
+        assert_eq!(
+            bracket.check(&rules, &candle(100.0, 106.0, 94.0, 101.0)),
+            Some((OrderType::Stop { stop_price: 95.0 }, ExitReason::StopLoss))
+        );
+        assert_eq!(
+            bracket.check(&rules, &candle(100.0, 106.0, 99.0, 101.0)),
+            Some((OrderType::Limit { limit_price: 105.0 }, ExitReason::TakeProfit))
+        );
+    }
+
+    #[test]
+    fn test_entry_bar_checks_levels_only() {
+        let rules = ExitRules { stop_loss: Some(ExitLevel::Percent(0.05)), max_bars: Some(1), ..ExitRules::default() };
+        let mut bracket = Bracket::new(&rules, Side::Buy, 100.0, None);
+        assert_eq!(bracket.check_entry_bar(&rules, &candle(100.0, 101.0, 99.0, 100.0)), None);
+        assert_eq!(bracket.bars_held, 0);
+        assert_eq!(
+            bracket.check_entry_bar(&rules, &candle(100.0, 101.0, 94.0, 96.0)),
+            Some((OrderType::Stop { stop_price: 95.0 }, ExitReason::StopLoss))
+        );
+    }
+
+    #[test]
+    fn test_trailing_stop_follows_best_price() {
+        let rules = ExitRules { trailing_stop: Some(0.1), ..ExitRules::default() };
+        let mut bracket = Bracket::new(&rules, Side::Sell, 100.0, None);
+        assert_eq!(bracket.check(&rules, &candle(100.0, 101.0, 80.0, 82.0)), None);
+        assert_eq!(bracket.best_price, 80.0);
+        assert_eq!(
+            bracket.check(&rules, &candle(82.0, 90.0, 81.0, 89.0)),
+            Some((OrderType::Stop { stop_price: 88.0 }, ExitReason::TrailingStop))
+        );
+    }
+
+    #[test]
+    fn test_time_exit() {
+        let rules = ExitRules { max_bars: Some(2), ..ExitRules::default() };
+        let mut bracket = Bracket::new(&rules, Side::Buy, 100.0, None);
+        assert_eq!(bracket.check(&rules, &candle(100.0, 101.0, 99.0, 100.0)), None);
+        assert_eq!(bracket.check(&rules, &candle(100.0, 101.0, 99.0, 100.0)), Some((OrderType::Market, ExitReason::TimeExit)));
+    }
+
+    #[test]
+    fn test_rolling_atr() {
+        let mut atr = RollingAtr::new(2);
+        atr.update(&candle(10.0, 11.0, 9.0, 10.0));
+        assert_eq!(atr.value(), None);
+        atr.update(&candle(10.0, 12.0, 10.0, 11.0));
+        assert_eq!(atr.value(), Some(2.0));
+        // Gapping up from the close of 11, the true range reaches back to it
+        atr.update(&candle(13.0, 15.0, 13.0, 14.0));
+        assert_eq!(atr.value(), Some(3.0));
+    }
+}
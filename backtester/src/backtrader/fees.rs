@@ -0,0 +1,185 @@
+use std::collections::{HashMap, VecDeque};
+use polars::prelude::{PolarsError, PolarsResult};
+use serde::Deserialize;
+use crate::backtrader::order::Liquidity;
+use crate::performance::frequency::MS_PER_DAY;
+
+// Fee tables of the built in exchanges, compiled in so the exchange profiles and the config file cannot drift apart
+const BUILT_IN_SCHEDULES: &str = include_str!("../../examples/config/fees.json");
+
+// Exchanges place accounts in a tier by their quote volume over the trailing 30 days
+pub const VOLUME_WINDOW_MS: i64 = 30 * MS_PER_DAY;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FeeTier {
+    pub min_volume: f64, // 30 day quote volume from which the tier applies
+    pub maker: f64,      // Fraction of the notional, 0.001 is 0.1%
+    pub taker: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FeeSchedule {
+    pub tiers: Vec<FeeTier>,
+    #[serde(default)]
+    pub fee_token_discount: f64, // Share of the fee waived when paying in the exchange's token, e.g. BNB
+    #[serde(default)]
+    pub pay_with_fee_token: bool,
+    #[serde(default)]
+    pub minimum_fee: f64, // Floor per fill in quote currency
+    #[serde(default)]
+    pub symbols: HashMap<String, Vec<FeeTier>>, // Tier tables replacing `tiers` for specific symbols
+}
+
+impl FeeSchedule {
+    // Flat fee for every fill, what `Exchange` charges without a schedule
+    pub fn flat(commission_pct: f64, commission_fixed: f64) -> Self {
+        FeeSchedule {
+            tiers: vec![FeeTier { min_volume: 0.0, maker: commission_pct, taker: commission_pct }],
+            fee_token_discount: 0.0,
+            pay_with_fee_token: false,
+            minimum_fee: commission_fixed,
+            symbols: HashMap::new(),
+        }
+    }
+
+    /// Read the schedule of `exchange` from a JSON file keyed by lowercase exchange name,
+    /// see `examples/config/fees.json` for the Binance, Kraken, Coinbase and Bybit tables.
+    pub fn from_file(path: &str, exchange: &str) -> PolarsResult<Self> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|error| PolarsError::ComputeError(format!("Could not read fee config '{}': {}", path, error).into()))?;
+        Self::parse(&config, path, exchange)
+    }
+
+    // Schedule of a built in exchange profile, read from the `examples/config/fees.json` compiled into the crate
+    pub fn built_in(exchange: &str) -> Self {
+        Self::parse(BUILT_IN_SCHEDULES, "examples/config/fees.json", exchange)
+            .unwrap_or_else(|error| panic!("Built in fee tables are broken: {}", error))
+    }
+
+    fn parse(config: &str, path: &str, exchange: &str) -> PolarsResult<Self> {
+        let mut schedules: HashMap<String, FeeSchedule> = serde_json::from_str(config)
+            .map_err(|error| PolarsError::ComputeError(format!("Invalid fee config '{}': {}", path, error).into()))?;
+        let mut schedule = schedules
+            .remove(&exchange.to_lowercase())
+            .ok_or_else(|| PolarsError::ComputeError(format!("No fee schedule for '{}' in '{}'", exchange, path).into()))?;
+        schedule.sort_tiers();
+        Ok(schedule)
+    }
+
+    fn sort_tiers(&mut self) {
+        self.tiers.sort_by(|a, b| a.min_volume.total_cmp(&b.min_volume));
+        for tiers in self.symbols.values_mut() {
+            tiers.sort_by(|a, b| a.min_volume.total_cmp(&b.min_volume));
+        }
+    }
+
+    // Fee rate of a fill after the token discount, from the highest tier the trailing volume reaches
+    pub fn rate(&self, symbol: &str, liquidity: Liquidity, trailing_volume: f64) -> f64 {
+        let tiers = self.symbols.get(symbol).unwrap_or(&self.tiers);
+        let Some(tier) = tiers.iter().rev().find(|tier| trailing_volume >= tier.min_volume).or(tiers.first()) else {
+            return 0.0;
+        };
+        let rate = match liquidity {
+            Liquidity::Maker => tier.maker,
+            Liquidity::Taker => tier.taker,
+        };
+        if self.pay_with_fee_token {
+            rate * (1.0 - self.fee_token_discount)
+        } else {
+            rate
+        }
+    }
+
+    pub fn fee(&self, symbol: &str, liquidity: Liquidity, trade_value: f64, trailing_volume: f64) -> f64 {
+        (trade_value * self.rate(symbol, liquidity, trailing_volume)).max(self.minimum_fee)
+    }
+}
+
+// Quote volume of the fills inside the trailing window
+#[derive(Debug, Clone, Default)]
+pub struct VolumeTracker {
+    fills: VecDeque<(i64, f64)>,
+}
+
+impl VolumeTracker {
+    // Trailing volume at a fill, fills that left the window are dropped for good
+    pub fn trailing_volume(&mut self, timestamp: i64) -> f64 {
+        while self.fills.front().is_some_and(|&(filled_at, _)| timestamp - filled_at >= VOLUME_WINDOW_MS) {
+            self.fills.pop_front();
+        }
+        self.volume_at(timestamp)
+    }
+
+    // Trailing volume without dropping old fills, for sizing an order before it fills
+    pub fn volume_at(&self, timestamp: i64) -> f64 {
+        self.fills
+            .iter()
+            .filter(|(filled_at, _)| timestamp - filled_at < VOLUME_WINDOW_MS)
+            .map(|(_, value)| value)
+            .sum::<f64>()
+            .max(0.0)
+    }
+
+    pub fn record(&mut self, timestamp: i64, trade_value: f64) {
+        self.fills.push_back((timestamp, trade_value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule() -> FeeSchedule {
+        FeeSchedule {
+            tiers: vec![
+                FeeTier { min_volume: 0.0, maker: 0.002, taker: 0.004 },
+                FeeTier { min_volume: 10_000.0, maker: 0.001, taker: 0.002 },
+            ],
+            fee_token_discount: 0.25,
+            pay_with_fee_token: false,
+            minimum_fee: 0.5,
+            symbols: HashMap::from([("USDCUSD".to_string(), vec![FeeTier { min_volume: 0.0, maker: 0.0, taker: 0.0001 }])]),
+        }
+    }
+
+    #[test]
+    fn test_tiers_and_overrides() {
+        let mut schedule = schedule();
+        assert_eq!(schedule.rate("BTCUSD", Liquidity::Taker, 0.0), 0.004);
+        assert_eq!(schedule.rate("BTCUSD", Liquidity::Maker, 25_000.0), 0.001);
+        assert_eq!(schedule.rate("USDCUSD", Liquidity::Taker, 25_000.0), 0.0001);
+        assert_eq!(schedule.fee("BTCUSD", Liquidity::Taker, 1000.0, 0.0), 4.0);
+        // Small fills pay the minimum
+        assert_eq!(schedule.fee("BTCUSD", Liquidity::Maker, 10.0, 0.0), 0.5);
+
+        schedule.pay_with_fee_token = true;
+        assert_eq!(schedule.rate("BTCUSD", Liquidity::Taker, 0.0), 0.003);
+    }
+
+    #[test]
+    fn test_trailing_volume_window() {
+        let mut tracker = VolumeTracker::default();
+        tracker.record(0, 6000.0);
+        tracker.record(MS_PER_DAY, 6000.0);
+        assert_eq!(tracker.trailing_volume(2 * MS_PER_DAY), 12_000.0);
+        assert_eq!(tracker.volume_at(VOLUME_WINDOW_MS), 6000.0);
+        // The first fill drops out 30 days later
+        assert_eq!(tracker.trailing_volume(VOLUME_WINDOW_MS), 6000.0);
+        assert_eq!(tracker.trailing_volume(VOLUME_WINDOW_MS + MS_PER_DAY), 0.0);
+    }
+
+    #[test]
+    fn test_load_example_config() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/config/fees.json");
+        let kraken = FeeSchedule::from_file(path, "Kraken").unwrap();
+        assert_eq!(kraken.rate("BTCUSD", Liquidity::Taker, 0.0), 0.004);
+        assert_eq!(kraken.rate("BTCUSD", Liquidity::Maker, 60_000.0), 0.0014);
+
+        assert!(FeeSchedule::from_file(path, "Bitstamp").is_err());
+
+        // The built in schedules are the config's tables, the 100M Binance tier included
+        let binance = FeeSchedule::built_in("binance");
+        assert_eq!(binance, FeeSchedule::from_file(path, "binance").unwrap());
+        assert_eq!(binance.rate("BTCUSDT", Liquidity::Taker, 150_000_000.0), 0.00054);
+    }
+}
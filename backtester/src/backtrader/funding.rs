@@ -0,0 +1,87 @@
+use polars::prelude::*;
+use crate::data::csv::load_csv;
+
+// Funding of a perpetual future, a positive rate has longs pay shorts
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FundingRates {
+    pub rates: Vec<(i64, f64)>, // (funding timestamp in ms, rate per funding interval), sorted by timestamp
+}
+
+// A funding settlement on a position, negative amounts were paid and positive ones received
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingPayment {
+    pub symbol: String,
+    pub timestamp: i64,
+    pub rate: f64,
+    pub position: f64, // Signed position held at the funding timestamp
+    pub price: f64,    // Mark price the position was valued at
+    pub amount: f64,
+}
+
+impl FundingRates {
+    pub fn new(mut rates: Vec<(i64, f64)>) -> Self {
+        rates.sort_by_key(|rate| rate.0);
+        Self { rates }
+    }
+
+    // Rates of a frame with `timestamp` and `funding_rate` columns
+    pub fn from_dataframe(df: &DataFrame) -> PolarsResult<Self> {
+        let timestamps = df.column("timestamp")?.cast(&DataType::Int64)?;
+        let rates = df.column("funding_rate")?.cast(&DataType::Float64)?;
+        Ok(Self::new(
+            timestamps
+                .i64()?
+                .into_iter()
+                .zip(rates.f64()?)
+                .filter_map(|(timestamp, rate)| Some((timestamp?, rate?)))
+                .collect(),
+        ))
+    }
+
+    pub fn from_csv(file_path: &str) -> PolarsResult<Self> {
+        Self::from_dataframe(&load_csv(file_path)?)
+    }
+
+    // Fundings settled after `previous` up to and including `timestamp`
+    pub fn between(&self, previous: i64, timestamp: i64) -> &[(i64, f64)] {
+        let start = self.rates.partition_point(|rate| rate.0 <= previous);
+        let end = self.rates.partition_point(|rate| rate.0 <= timestamp);
+        &self.rates[start..end.max(start)]
+    }
+}
+
+// Cash a position receives at a funding, longs pay a positive rate and shorts receive it
+pub fn funding_amount(position: f64, price: f64, rate: f64) -> f64 {
+    -position * price * rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_between() {
+        let funding = FundingRates::new(vec![(16, 0.0003), (0, 0.0001), (8, -0.0002)]);
+        assert_eq!(funding.between(0, 8), &[(8, -0.0002)]);
+        assert_eq!(funding.between(-1, 16).len(), 3);
+        assert!(funding.between(16, 24).is_empty());
+        assert!(funding.between(9, 15).is_empty());
+    }
+
+    #[test]
+    fn test_funding_amount() {
+        assert!((funding_amount(2.0, 100.0, 0.0001) + 0.02).abs() < 1e-12);
+        assert!((funding_amount(-2.0, 100.0, 0.0001) - 0.02).abs() < 1e-12);
+        assert!((funding_amount(2.0, 100.0, -0.0001) - 0.02).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_from_dataframe() {
+        let frame = df!(
+            "timestamp" => [28_800_000i64, 0],
+            "funding_rate" => [0.0002, 0.0001],
+        ).unwrap();
+        let funding = FundingRates::from_dataframe(&frame).unwrap();
+        assert_eq!(funding.rates, vec![(0, 0.0001), (28_800_000, 0.0002)]);
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use polars::prelude::*;
+use crate::backtrader::order::Side;
+use crate::backtrader::order_book::{Fill, OrderId};
+
+// A round trip from opening a position to closing it, `side` is the opening side so Buy is long and Sell is short
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub symbol: String,
+    pub side: Side,
+    pub quantity: f64,
+    pub entry_time: i64,
+    pub entry_price: f64, // Average over every fill that added to the position
+    pub exit_time: Option<i64>,
+    pub exit_price: Option<f64>, // Partial closes are booked as separate trades, each with their own exit
+    pub commission: f64,
+    pub slippage: f64,    // Already paid through the fill prices, kept apart to see what execution cost
+    pub pnl: Option<f64>, // Realized, net of commission and slippage, None while the trade is open
+}
+
+// A fill refused for breaking the symbol's lot size or minimum size, the order is dropped
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    pub order_id: OrderId,
+    pub symbol: String,
+    pub side: Side,
+    pub timestamp: i64,
+    pub price: f64,
+    pub quantity: f64, // After rounding down to the lot size
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct TradeLedger {
+    closed: Vec<Trade>,
+    open: HashMap<String, Trade>, // At most one open trade per symbol, it is netted like the position
+    rejections: Vec<Rejection>,
+}
+
+impl TradeLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Book a fill against the symbol's open trade.
+    /// Fills on the same side add to it, opposite fills close it and any remainder opens a trade the other way.
+    pub fn record_fill(&mut self, fill: &Fill) {
+        let mut quantity = fill.quantity;
+
+        if let Some(mut trade) = self.open.remove(&fill.symbol) {
+            if trade.side == fill.side {
+                let total = trade.quantity + quantity;
+                trade.entry_price = (trade.entry_price * trade.quantity + fill.price * quantity) / total;
+                trade.quantity = total;
+                trade.commission += fill.commission;
+                trade.slippage += fill.slippage;
+                self.open.insert(fill.symbol.clone(), trade);
+                return;
+            }
+
+            let closing = quantity.min(trade.quantity);
+            let share = closing / quantity;
+            let remaining = trade.quantity - closing;
+
+            // Split the entry costs between the part that is closed and the part that stays open
+            let mut closed = trade.clone();
+            closed.quantity = closing;
+            closed.commission = trade.commission * closing / trade.quantity + fill.commission * share;
+            closed.slippage = trade.slippage * closing / trade.quantity + fill.slippage * share;
+            closed.exit_time = Some(fill.timestamp);
+            closed.exit_price = Some(fill.price);
+            let gross = (fill.price - trade.entry_price) * closing * trade.side.direction();
+            // Fill prices include slippage, so only the commission is taken off the gross
+            closed.pnl = Some(gross - closed.commission);
+            self.closed.push(closed);
+
+            if remaining > 0.0 {
+                trade.commission *= remaining / trade.quantity;
+                trade.slippage *= remaining / trade.quantity;
+                trade.quantity = remaining;
+                self.open.insert(fill.symbol.clone(), trade);
+                return;
+            }
+
+            quantity -= closing;
+            if quantity <= 0.0 {
+                return;
+            }
+        }
+
+        let share = quantity / fill.quantity;
+        self.open.insert(fill.symbol.clone(), Trade {
+            symbol: fill.symbol.clone(),
+            side: fill.side,
+            quantity,
+            entry_time: fill.timestamp,
+            entry_price: fill.price,
+            exit_time: None,
+            exit_price: None,
+            commission: fill.commission * share,
+            slippage: fill.slippage * share,
+            pnl: None,
+        });
+    }
+
+    pub fn record_rejection(&mut self, rejection: Rejection) {
+        self.rejections.push(rejection);
+    }
+
+    pub fn rejections(&self) -> &[Rejection] {
+        &self.rejections
+    }
+
+    pub fn trades(&self) -> &[Trade] {
+        &self.closed
+    }
+
+    pub fn open_trade(&self, symbol: &str) -> Option<&Trade> {
+        self.open.get(symbol)
+    }
+
+    // Open trades in the order they were entered, symbols entered on the same bar by name
+    pub fn open_trades(&self) -> Vec<&Trade> {
+        let mut trades: Vec<&Trade> = self.open.values().collect();
+        trades.sort_by(|a, b| a.entry_time.cmp(&b.entry_time).then_with(|| a.symbol.cmp(&b.symbol)));
+        trades
+    }
+
+    // Closed trades in the order they were closed, followed by the open ones
+    pub fn to_dataframe(&self) -> PolarsResult<DataFrame> {
+        let trades: Vec<&Trade> = self.closed.iter().chain(self.open_trades()).collect();
+        let datetime = DataType::Datetime(TimeUnit::Milliseconds, None);
+
+        df!(
+            "symbol" => trades.iter().map(|trade| trade.symbol.as_str()).collect::<Vec<_>>(),
+            "side" => trades.iter().map(|trade| trade.side.as_str()).collect::<Vec<_>>(),
+            "quantity" => trades.iter().map(|trade| trade.quantity).collect::<Vec<_>>(),
+            "entry_time" => trades.iter().map(|trade| trade.entry_time).collect::<Vec<_>>(),
+            "entry_price" => trades.iter().map(|trade| trade.entry_price).collect::<Vec<_>>(),
+            "exit_time" => trades.iter().map(|trade| trade.exit_time).collect::<Vec<_>>(),
+            "exit_price" => trades.iter().map(|trade| trade.exit_price).collect::<Vec<_>>(),
+            "commission" => trades.iter().map(|trade| trade.commission).collect::<Vec<_>>(),
+            "slippage" => trades.iter().map(|trade| trade.slippage).collect::<Vec<_>>(),
+            "pnl" => trades.iter().map(|trade| trade.pnl).collect::<Vec<_>>(),
+        )?
+            .lazy()
+            .with_columns([
+                col("entry_time").cast(datetime.clone()),
+                col("exit_time").cast(datetime),
+            ])
+            .collect()
+    }
+
+    // Orders the exchange refused, in the order they were rejected
+    pub fn rejections_to_dataframe(&self) -> PolarsResult<DataFrame> {
+        df!(
+            "order_id" => self.rejections.iter().map(|rejection| rejection.order_id).collect::<Vec<_>>(),
+            "symbol" => self.rejections.iter().map(|rejection| rejection.symbol.as_str()).collect::<Vec<_>>(),
+            "side" => self.rejections.iter().map(|rejection| rejection.side.as_str()).collect::<Vec<_>>(),
+            "timestamp" => self.rejections.iter().map(|rejection| rejection.timestamp).collect::<Vec<_>>(),
+            "price" => self.rejections.iter().map(|rejection| rejection.price).collect::<Vec<_>>(),
+            "quantity" => self.rejections.iter().map(|rejection| rejection.quantity).collect::<Vec<_>>(),
+            "reason" => self.rejections.iter().map(|rejection| rejection.reason.as_str()).collect::<Vec<_>>(),
+        )?
+            .lazy()
+            .with_column(col("timestamp").cast(DataType::Datetime(TimeUnit::Milliseconds, None)))
+            .collect()
+    }
+}
+
+// Timestamped equity of a symbol, one point per bar
+pub fn equity_curve_to_dataframe(curve: &[(i64, f64)]) -> PolarsResult<DataFrame> {
+    df!(
+        "timestamp" => curve.iter().map(|point| point.0).collect::<Vec<_>>(),
+        "equity" => curve.iter().map(|point| point.1).collect::<Vec<_>>(),
+    )?
+        .lazy()
+        .with_column(col("timestamp").cast(DataType::Datetime(TimeUnit::Milliseconds, None)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(side: Side, timestamp: i64, price: f64, quantity: f64) -> Fill {
+        Fill {
+            order_id: 1,
+            symbol: "BTCUSDT".to_string(),
+            side,
+            timestamp,
+            price,
+            quantity,
+            commission: 1.0,
+            slippage: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut ledger = TradeLedger::new();
+        ledger.record_fill(&fill(Side::Buy, 0, 100.0, 1.0));
+        ledger.record_fill(&fill(Side::Buy, 1, 110.0, 1.0));
+        assert_eq!(ledger.open_trades()[0].entry_price, 105.0);
+
+        ledger.record_fill(&fill(Side::Sell, 2, 120.0, 2.0));
+        let trade = &ledger.trades()[0];
+        assert_eq!(trade.quantity, 2.0);
+        assert_eq!(trade.exit_time, Some(2));
+        assert_eq!(trade.commission, 3.0);
+        assert_eq!(trade.pnl, Some(30.0 - 3.0));
+        assert!(ledger.open_trades().is_empty());
+    }
+
+    #[test]
+    fn test_reversal_opens_short() {
+        let mut ledger = TradeLedger::new();
+        ledger.record_fill(&fill(Side::Buy, 0, 100.0, 1.0));
+        ledger.record_fill(&fill(Side::Sell, 1, 90.0, 3.0));
+
+        let long = &ledger.trades()[0];
+        assert!((long.pnl.unwrap() - (-10.0 - 1.0 - 1.0 / 3.0)).abs() < 1e-9);
+
+        let short = ledger.open_trades()[0];
+        assert_eq!(short.side, Side::Sell);
+        assert_eq!(short.quantity, 2.0);
+        assert_eq!(short.entry_price, 90.0);
+
+        ledger.record_fill(&fill(Side::Buy, 2, 80.0, 2.0));
+        assert!((ledger.trades()[1].pnl.unwrap() - (20.0 - 2.0 / 3.0 - 1.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_partial_close() {
+        let mut ledger = TradeLedger::new();
+        ledger.record_fill(&fill(Side::Buy, 0, 100.0, 4.0));
+        ledger.record_fill(&fill(Side::Sell, 1, 110.0, 1.0));
+
+        assert_eq!(ledger.trades()[0].quantity, 1.0);
+        assert_eq!(ledger.trades()[0].pnl, Some(10.0 - 0.25 - 1.0));
+        assert_eq!(ledger.open_trades()[0].quantity, 3.0);
+        assert_eq!(ledger.open_trades()[0].commission, 0.75);
+
+        let frame = ledger.to_dataframe().unwrap();
+        assert_eq!(frame.height(), 2);
+        assert_eq!(frame.column("pnl").unwrap().null_count(), 1);
+    }
+
+    #[test]
+    fn test_open_trades_in_entry_order() {
+        let mut ledger = TradeLedger::new();
+        for (symbol, timestamp) in [("SOLUSDT", 1), ("ETHUSDT", 0), ("BTCUSDT", 1), ("ADAUSDT", 2)] {
+            ledger.record_fill(&Fill { symbol: symbol.to_string(), ..fill(Side::Buy, timestamp, 100.0, 1.0) });
+        }
+
+        let frame = ledger.to_dataframe().unwrap();
+        let symbols: Vec<&str> = frame.column("symbol").unwrap().str().unwrap().into_no_null_iter().collect();
+        assert_eq!(symbols, ["ETHUSDT", "BTCUSDT", "SOLUSDT", "ADAUSDT"]);
+    }
+
+    #[test]
+    fn test_rejections() {
+        let mut ledger = TradeLedger::new();
+        ledger.record_rejection(Rejection {
+            order_id: 3,
+            symbol: "BTCUSDT".to_string(),
+            side: Side::Buy,
+            timestamp: 0,
+            price: 100.0,
+            quantity: 0.01,
+            reason: "notional 1.00 below the minimum of 5".to_string(),
+        });
+
+        assert_eq!(ledger.rejections()[0].order_id, 3);
+        assert!(ledger.trades().is_empty());
+        let frame = ledger.rejections_to_dataframe().unwrap();
+        assert_eq!(frame.height(), 1);
+        assert_eq!(frame.column("reason").unwrap().str().unwrap().get(0), Some("notional 1.00 below the minimum of 5"));
+    }
+}
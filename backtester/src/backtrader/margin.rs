@@ -0,0 +1,82 @@
+use crate::performance::frequency::{DAYS_PER_YEAR, MS_PER_DAY};
+
+const MS_PER_YEAR: f64 = DAYS_PER_YEAR * MS_PER_DAY as f64;
+
+// Margin terms for an asset, all expressed as fractions of the position's notional
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarginConfig {
+    pub initial_margin: f64,     // Equity needed to open exposure, 0.5 allows 2x leverage
+    pub maintenance_margin: f64, // Below this fraction of exposure in equity the position is liquidated
+    pub borrow_rate: f64,        // Yearly rate on borrowed assets (shorts) and borrowed cash (leveraged longs)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Liquidation {
+    pub symbol: String,
+    pub timestamp: i64,
+    pub price: f64,
+    pub quantity: f64, // Signed position that was closed, negative for shorts
+    pub equity: f64,   // Equity left after the position was closed
+}
+
+impl MarginConfig {
+    // Fee for holding the position for `elapsed_ms`, shorts borrow the asset and leveraged longs borrow cash
+    pub fn borrow_fee(&self, cash: f64, positions: f64, price: f64, elapsed_ms: i64) -> f64 {
+        let borrowed = if positions < 0.0 {
+            -positions * price
+        } else {
+            (-cash).max(0.0)
+        };
+        borrowed * self.borrow_rate * elapsed_ms as f64 / MS_PER_YEAR
+    }
+
+    /// Price at which equity falls below the maintenance margin of the exposure.
+    /// Longs are liquidated at or below it, shorts at or above it, None when the position can not be liquidated.
+    pub fn liquidation_price(&self, cash: f64, positions: f64) -> Option<f64> {
+        if positions > 0.0 {
+            // cash + q * p = m * q * p
+            let price = -cash / (positions * (1.0 - self.maintenance_margin));
+            (price > 0.0).then_some(price)
+        } else if positions < 0.0 {
+            // cash + q * p = m * -q * p
+            Some(cash / (-positions * (1.0 + self.maintenance_margin)))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARGIN: MarginConfig = MarginConfig {
+        initial_margin: 0.5,
+        maintenance_margin: 0.25,
+        borrow_rate: 0.1,
+    };
+
+    #[test]
+    fn test_liquidation_price() {
+        // Short 10 at 100 on 1000 equity: cash 2000, liquidated once equity is 25% of exposure
+        let short = MARGIN.liquidation_price(2000.0, -10.0).unwrap();
+        assert_eq!(short, 160.0);
+        assert!((2000.0 - 10.0 * short - 0.25 * 10.0 * short).abs() < 1e-9);
+
+        // Long 20 at 100 on 1000 equity: 1000 of cash borrowed
+        let long = MARGIN.liquidation_price(-1000.0, 20.0).unwrap();
+        assert!((long - 200.0 / 3.0).abs() < 1e-9);
+
+        // A long paid in full can not be liquidated
+        assert_eq!(MARGIN.liquidation_price(0.0, 10.0), None);
+        assert_eq!(MARGIN.liquidation_price(1000.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_borrow_fee() {
+        let year = MS_PER_YEAR as i64;
+        assert!((MARGIN.borrow_fee(2000.0, -10.0, 100.0, year) - 100.0).abs() < 1e-9);
+        assert!((MARGIN.borrow_fee(-1000.0, 20.0, 100.0, year) - 100.0).abs() < 1e-9);
+        assert_eq!(MARGIN.borrow_fee(1000.0, 10.0, 100.0, year), 0.0);
+    }
+}
@@ -1,3 +1,15 @@
+#[allow(clippy::module_inception)]
 pub mod backtrader;
 pub mod exchange;
-pub mod asset_data;
\ No newline at end of file
+pub mod exchange_profile;
+pub mod asset_data;
+pub mod order;
+pub mod order_book;
+pub mod position_sizer;
+pub mod margin;
+pub mod ledger;
+pub mod rebalance;
+pub mod slippage;
+pub mod fees;
+pub mod funding;
+pub mod exits;
\ No newline at end of file
@@ -0,0 +1,280 @@
+use std::str::FromStr;
+use polars::prelude::*;
+use crate::data::candle::Candle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+// Whether a fill added liquidity to the book or took it, exchanges charge them differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeInForce {
+    GTC, // Good till cancelled, rests across bars until filled
+    IOC, // Immediate or cancel, fills what the first bar's volume allows and drops the rest
+    FOK, // Fill or kill, fills completely on the first bar or is dropped
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderType {
+    Market,
+    Limit { limit_price: f64 },
+    Stop { stop_price: f64 },
+    StopLimit { stop_price: f64, limit_price: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Order {
+    pub symbol: String,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub time_in_force: TimeInForce,
+    pub quantity: Option<f64>, // None sizes from the asset: all cash on buys, all positions on sells
+}
+
+impl Side {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        }
+    }
+
+    pub const fn opposite(&self) -> Side {
+        match self {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        }
+    }
+
+    // Sign of the position change, +1 for buys and -1 for sells
+    pub const fn direction(&self) -> f64 {
+        match self {
+            Side::Buy => 1.0,
+            Side::Sell => -1.0,
+        }
+    }
+}
+
+impl FromStr for TimeInForce {
+    type Err = PolarsError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "gtc" => Ok(TimeInForce::GTC),
+            "ioc" => Ok(TimeInForce::IOC),
+            "fok" => Ok(TimeInForce::FOK),
+            other => Err(PolarsError::ComputeError(format!("Unknown time in force '{}'", other).into())),
+        }
+    }
+}
+
+impl OrderType {
+    // Builds the order type from the strategy's `order_type` column and its price columns
+    pub fn parse(kind: &str, limit_price: Option<f64>, stop_price: Option<f64>) -> PolarsResult<Self> {
+        let missing = |column: &str| PolarsError::ComputeError(
+            format!("Order type '{}' requires a '{}' value", kind, column).into()
+        );
+
+        match kind.to_lowercase().as_str() {
+            "market" => Ok(OrderType::Market),
+            "limit" => Ok(OrderType::Limit {
+                limit_price: limit_price.ok_or_else(|| missing("limit_price"))?,
+            }),
+            "stop" => Ok(OrderType::Stop {
+                stop_price: stop_price.ok_or_else(|| missing("stop_price"))?,
+            }),
+            "stop_limit" => Ok(OrderType::StopLimit {
+                stop_price: stop_price.ok_or_else(|| missing("stop_price"))?,
+                limit_price: limit_price.ok_or_else(|| missing("limit_price"))?,
+            }),
+            other => Err(PolarsError::ComputeError(format!("Unknown order type '{}'", other).into())),
+        }
+    }
+}
+
+impl Order {
+    pub fn market(symbol: &str, side: Side) -> Self {
+        Order {
+            symbol: symbol.to_string(),
+            side,
+            order_type: OrderType::Market,
+            time_in_force: TimeInForce::GTC,
+            quantity: None,
+        }
+    }
+
+    pub const fn is_market(&self) -> bool {
+        matches!(self.order_type, OrderType::Market)
+    }
+
+    // Only a limit order resting until the market comes to its price adds liquidity, anything crossing takes it
+    pub fn liquidity(&self, fill_price: f64) -> Liquidity {
+        match self.order_type {
+            OrderType::Limit { limit_price } if fill_price == limit_price => Liquidity::Maker,
+            _ => Liquidity::Taker,
+        }
+    }
+
+    // Clamp an execution price to the order's limit, a limit buy never pays more and a limit sell never gets less
+    pub const fn within_limit(&self, price: f64) -> f64 {
+        match (self.order_type, self.side) {
+            (OrderType::Limit { limit_price } | OrderType::StopLimit { limit_price, .. }, Side::Buy) => price.min(limit_price),
+            (OrderType::Limit { limit_price } | OrderType::StopLimit { limit_price, .. }, Side::Sell) => price.max(limit_price),
+            _ => price,
+        }
+    }
+
+    /// Match the order against a candle and return the price it fills at, if any.
+    /// Gaps through the trigger fill at the open, as the exchange would have.
+    /// A stop-limit that triggers but cannot fill within its limit is turned into a resting limit order.
+    pub fn fill_price(&mut self, candle: &Candle) -> Option<f64> {
+        match (self.order_type, self.side) {
+            (OrderType::Market, _) => Some(candle.open),
+            (OrderType::Limit { limit_price }, Side::Buy) => {
+                (candle.low <= limit_price).then(|| candle.open.min(limit_price))
+            }
+            (OrderType::Limit { limit_price }, Side::Sell) => {
+                (candle.high >= limit_price).then(|| candle.open.max(limit_price))
+            }
+            (OrderType::Stop { stop_price }, Side::Buy) => {
+                (candle.high >= stop_price).then(|| candle.open.max(stop_price))
+            }
+            (OrderType::Stop { stop_price }, Side::Sell) => {
+                (candle.low <= stop_price).then(|| candle.open.min(stop_price))
+            }
+            (OrderType::StopLimit { stop_price, limit_price }, side) => {
+                let trigger_price = match side {
+                    Side::Buy if candle.high >= stop_price => candle.open.max(stop_price),
+                    Side::Sell if candle.low <= stop_price => candle.open.min(stop_price),
+                    _ => return None,
+                };
+
+                let within_limit = match side {
+                    Side::Buy => trigger_price <= limit_price,
+                    Side::Sell => trigger_price >= limit_price,
+                };
+
+                if within_limit {
+                    Some(trigger_price)
+                } else {
+                    self.order_type = OrderType::Limit { limit_price };
+                    None
+                }
+            }
+        }
+    }
+}
+
+// Optional order columns a strategy can emit next to `signal`; rows without them become market orders
+pub const ORDER_COLUMNS: [&str; 4] = ["order_type", "limit_price", "stop_price", "time_in_force"];
+
+pub struct OrderColumns<'a> {
+    order_type: Option<&'a StringChunked>,
+    limit_price: Option<&'a Float64Chunked>,
+    stop_price: Option<&'a Float64Chunked>,
+    time_in_force: Option<&'a StringChunked>,
+}
+
+impl<'a> OrderColumns<'a> {
+    pub fn new(df: &'a DataFrame) -> PolarsResult<Self> {
+        let schema = df.schema();
+        Ok(Self {
+            order_type: if schema.contains("order_type") { Some(df.column("order_type")?.str()?) } else { None },
+            limit_price: if schema.contains("limit_price") { Some(df.column("limit_price")?.f64()?) } else { None },
+            stop_price: if schema.contains("stop_price") { Some(df.column("stop_price")?.f64()?) } else { None },
+            time_in_force: if schema.contains("time_in_force") { Some(df.column("time_in_force")?.str()?) } else { None },
+        })
+    }
+
+    pub fn order_at(&self, i: usize, symbol: &str, side: Side) -> PolarsResult<Order> {
+        let limit_price = self.limit_price.and_then(|column| column.get(i));
+        let stop_price = self.stop_price.and_then(|column| column.get(i));
+
+        let order_type = match self.order_type.and_then(|column| column.get(i)) {
+            Some(kind) => OrderType::parse(kind, limit_price, stop_price)?,
+            None => OrderType::Market,
+        };
+
+        let time_in_force = match self.time_in_force.and_then(|column| column.get(i)) {
+            Some(value) => value.parse()?,
+            None => TimeInForce::GTC,
+        };
+
+        Ok(Order {
+            symbol: symbol.to_string(),
+            side,
+            order_type,
+            time_in_force,
+            quantity: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::candle::candle;
+
+    fn order(side: Side, order_type: OrderType) -> Order {
+        Order { order_type, ..Order::market("BTCUSDT", side) }
+    }
+
+    #[test]
+    fn test_market_fills_at_open() {
+        let mut market = Order::market("BTCUSDT", Side::Buy);
+        assert_eq!(market.fill_price(&candle(100.0, 110.0, 90.0, 105.0)), Some(100.0));
+    }
+
+    #[test]
+    fn test_limit_orders() {
+        let mut buy = order(Side::Buy, OrderType::Limit { limit_price: 95.0 });
+        assert_eq!(buy.fill_price(&candle(100.0, 110.0, 96.0, 105.0)), None);
+        assert_eq!(buy.fill_price(&candle(100.0, 110.0, 90.0, 105.0)), Some(95.0));
+        // Gapping below the limit fills at the better open
+        assert_eq!(buy.fill_price(&candle(92.0, 99.0, 90.0, 95.0)), Some(92.0));
+
+        let mut sell = order(Side::Sell, OrderType::Limit { limit_price: 105.0 });
+        assert_eq!(sell.fill_price(&candle(100.0, 104.0, 90.0, 101.0)), None);
+        assert_eq!(sell.fill_price(&candle(100.0, 110.0, 90.0, 101.0)), Some(105.0));
+    }
+
+    #[test]
+    fn test_stop_orders() {
+        let mut buy = order(Side::Buy, OrderType::Stop { stop_price: 105.0 });
+        assert_eq!(buy.fill_price(&candle(100.0, 104.0, 90.0, 101.0)), None);
+        assert_eq!(buy.fill_price(&candle(100.0, 110.0, 90.0, 101.0)), Some(105.0));
+        // Gapping through the stop fills at the worse open
+        assert_eq!(buy.fill_price(&candle(108.0, 110.0, 107.0, 109.0)), Some(108.0));
+
+        let mut sell = order(Side::Sell, OrderType::Stop { stop_price: 95.0 });
+        assert_eq!(sell.fill_price(&candle(100.0, 110.0, 96.0, 101.0)), None);
+        assert_eq!(sell.fill_price(&candle(100.0, 110.0, 90.0, 101.0)), Some(95.0));
+    }
+
+    #[test]
+    fn test_stop_limit_rests_as_limit_after_gap() {
+        let mut buy = order(Side::Buy, OrderType::StopLimit { stop_price: 105.0, limit_price: 106.0 });
+        assert_eq!(buy.fill_price(&candle(100.0, 110.0, 90.0, 101.0)), Some(105.0));
+
+        let mut gapped = order(Side::Buy, OrderType::StopLimit { stop_price: 105.0, limit_price: 106.0 });
+        assert_eq!(gapped.fill_price(&candle(108.0, 112.0, 107.0, 110.0)), None);
+        assert_eq!(gapped.order_type, OrderType::Limit { limit_price: 106.0 });
+        assert_eq!(gapped.fill_price(&candle(107.0, 108.0, 104.0, 105.0)), Some(106.0));
+    }
+
+    #[test]
+    fn test_parse_order_columns() {
+        assert_eq!(OrderType::parse("limit", Some(10.0), None).unwrap(), OrderType::Limit { limit_price: 10.0 });
+        assert!(OrderType::parse("stop", Some(10.0), None).is_err());
+        assert!(OrderType::parse("iceberg", None, None).is_err());
+        assert_eq!("ioc".parse::<TimeInForce>().unwrap(), TimeInForce::IOC);
+    }
+}
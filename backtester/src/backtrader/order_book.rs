@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use crate::backtrader::order::{Order, Side, TimeInForce};
+use crate::data::candle::Candle;
+
+pub type OrderId = u64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingOrder {
+    pub id: OrderId,
+    pub order: Order,
+    pub submitted_at: i64, // Timestamp of the bar that placed the order
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub order_id: OrderId,
+    pub symbol: String,
+    pub side: Side,
+    pub timestamp: i64,
+    pub price: f64,
+    pub quantity: f64,
+    pub commission: f64,
+    pub slippage: f64, // Cost of the fill price against the reference price, in quote currency
+}
+
+// Orders keep resting per symbol across bars until they are filled, cancelled or expire
+#[derive(Debug, Default)]
+pub struct OrderBook {
+    next_id: OrderId,
+    pending: HashMap<String, Vec<PendingOrder>>,
+    fills: Vec<Fill>,
+}
+
+impl OrderBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn submit(&mut self, order: Order, submitted_at: i64) -> OrderId {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.pending
+            .entry(order.symbol.clone())
+            .or_default()
+            .push(PendingOrder { id, order, submitted_at });
+        id
+    }
+
+    pub fn cancel(&mut self, id: OrderId) -> Option<Order> {
+        for orders in self.pending.values_mut() {
+            if let Some(index) = orders.iter().position(|pending| pending.id == id) {
+                return Some(orders.remove(index).order);
+            }
+        }
+        None
+    }
+
+    pub fn cancel_all(&mut self, symbol: &str) -> Vec<Order> {
+        self.pending
+            .remove(symbol)
+            .map(|orders| orders.into_iter().map(|pending| pending.order).collect())
+            .unwrap_or_default()
+    }
+
+    // Cancel the order and submit the replacement under a new id, None if the order was no longer pending
+    pub fn replace(&mut self, id: OrderId, order: Order, submitted_at: i64) -> Option<OrderId> {
+        self.cancel(id)?;
+        Some(self.submit(order, submitted_at))
+    }
+
+    pub fn is_pending(&self, id: OrderId) -> bool {
+        self.pending.values().flatten().any(|pending| pending.id == id)
+    }
+
+    pub fn pending(&self, symbol: &str) -> &[PendingOrder] {
+        self.pending.get(symbol).map_or(&[], |orders| orders.as_slice())
+    }
+
+    /// Take every order of the symbol that the candle's range triggers, together with its fill price.
+    /// IOC and FOK orders that do not trigger on their first bar are dropped, GTC orders keep resting.
+    pub fn match_candle(&mut self, symbol: &str, candle: &Candle) -> Vec<(PendingOrder, f64)> {
+        let Some(orders) = self.pending.get_mut(symbol) else {
+            return vec![];
+        };
+
+        let mut triggered = vec![];
+        let mut resting = vec![];
+        for mut pending in orders.drain(..) {
+            if let Some(price) = pending.order.fill_price(candle) {
+                triggered.push((pending, price));
+            } else if pending.order.time_in_force == TimeInForce::GTC {
+                resting.push(pending);
+            }
+        }
+        *orders = resting;
+        triggered
+    }
+
+    pub fn record_fill(&mut self, fill: Fill) {
+        self.fills.push(fill);
+    }
+
+    pub fn fills(&self) -> &[Fill] {
+        &self.fills
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtrader::order::OrderType;
+    use crate::data::candle::candle;
+
+    fn limit(side: Side, limit_price: f64, time_in_force: TimeInForce) -> Order {
+        Order {
+            order_type: OrderType::Limit { limit_price },
+            time_in_force,
+            ..Order::market("BTCUSDT", side)
+        }
+    }
+
+    #[test]
+    fn test_orders_rest_until_triggered() {
+        let mut book = OrderBook::new();
+        let id = book.submit(limit(Side::Buy, 95.0, TimeInForce::GTC), 0);
+
+        assert!(book.match_candle("BTCUSDT", &candle(100.0, 101.0, 96.0, 100.0)).is_empty());
+        assert!(book.is_pending(id));
+
+        let triggered = book.match_candle("BTCUSDT", &candle(97.0, 98.0, 94.0, 96.0));
+        assert_eq!(triggered.len(), 1);
+        assert_eq!(triggered[0].0.id, id);
+        assert_eq!(triggered[0].1, 95.0);
+        assert!(book.pending("BTCUSDT").is_empty());
+    }
+
+    #[test]
+    fn test_ioc_expires_after_first_bar() {
+        let mut book = OrderBook::new();
+        let id = book.submit(limit(Side::Buy, 95.0, TimeInForce::IOC), 0);
+
+        assert!(book.match_candle("BTCUSDT", &candle(100.0, 101.0, 96.0, 100.0)).is_empty());
+        assert!(!book.is_pending(id));
+    }
+
+    #[test]
+    fn test_cancel_and_replace() {
+        let mut book = OrderBook::new();
+        let first = book.submit(limit(Side::Buy, 95.0, TimeInForce::GTC), 0);
+        let second = book.replace(first, limit(Side::Buy, 96.0, TimeInForce::GTC), 1).unwrap();
+
+        assert_ne!(first, second);
+        assert!(!book.is_pending(first));
+        assert_eq!(book.pending("BTCUSDT")[0].order.order_type, OrderType::Limit { limit_price: 96.0 });
+        assert!(book.replace(first, limit(Side::Buy, 97.0, TimeInForce::GTC), 2).is_none());
+
+        assert!(book.cancel(second).is_some());
+        assert!(book.pending("BTCUSDT").is_empty());
+    }
+}
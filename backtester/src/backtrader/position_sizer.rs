@@ -0,0 +1,146 @@
+use std::fmt::Debug;
+use crate::backtrader::asset_data::AssetData;
+
+pub struct SizingContext<'a> {
+    pub asset: &'a AssetData,
+    pub price: f64,       // Close of the bar that produced the signal
+    pub equity: f64,      // Cash plus positions marked at `price`
+    pub atr: Option<f64>, // Only set when the sizer asks for it through `atr_window`
+}
+
+pub trait PositionSizer: Debug {
+    // Target number of units to hold after a buy signal, the engine only orders the difference to the current position
+    fn size(&self, context: &SizingContext) -> f64;
+
+    // Window of the average true range the sizer needs
+    fn atr_window(&self) -> Option<usize> {
+        None
+    }
+}
+
+// Hold a fixed fraction of equity, 1.0 is all in
+#[derive(Debug, Clone)]
+pub struct FixedFraction {
+    pub fraction: f64,
+}
+
+impl PositionSizer for FixedFraction {
+    fn size(&self, context: &SizingContext) -> f64 {
+        context.equity * self.fraction / context.price
+    }
+}
+
+// Hold a fixed amount of quote currency regardless of equity
+#[derive(Debug, Clone)]
+pub struct FixedNotional {
+    pub notional: f64,
+}
+
+impl PositionSizer for FixedNotional {
+    fn size(&self, context: &SizingContext) -> f64 {
+        self.notional / context.price
+    }
+}
+
+// Risk `target_risk` of equity per ATR move, so positions shrink as volatility grows
+#[derive(Debug, Clone)]
+pub struct VolatilityTarget {
+    pub target_risk: f64,
+    pub atr_window: usize,
+}
+
+impl PositionSizer for VolatilityTarget {
+    fn size(&self, context: &SizingContext) -> f64 {
+        match context.atr {
+            Some(atr) if atr > 0.0 => context.equity * self.target_risk / atr,
+            _ => 0.0, // Not enough history to measure volatility yet
+        }
+    }
+
+    fn atr_window(&self) -> Option<usize> {
+        Some(self.atr_window)
+    }
+}
+
+// Kelly criterion f = p - (1 - p) / b, scaled by `fraction` since full Kelly is rarely survivable
+#[derive(Debug, Clone)]
+pub struct Kelly {
+    pub win_probability: f64,
+    pub win_loss_ratio: f64,
+    pub fraction: f64,
+}
+
+impl Kelly {
+    // Estimate the win probability and payoff ratio from the returns of earlier trades
+    pub fn from_trade_returns(trade_returns: &[f64], fraction: f64) -> Self {
+        let wins: Vec<f64> = trade_returns.iter().copied().filter(|r| *r > 0.0).collect();
+        let losses: Vec<f64> = trade_returns.iter().copied().filter(|r| *r < 0.0).collect();
+
+        let win_probability = if trade_returns.is_empty() {
+            0.0
+        } else {
+            wins.len() as f64 / trade_returns.len() as f64
+        };
+        let average_win = wins.iter().sum::<f64>() / wins.len().max(1) as f64;
+        let average_loss = losses.iter().sum::<f64>().abs() / losses.len().max(1) as f64;
+        let win_loss_ratio = if average_loss > 0.0 { average_win / average_loss } else { f64::INFINITY };
+
+        Kelly { win_probability, win_loss_ratio, fraction }
+    }
+
+    pub fn kelly_fraction(&self) -> f64 {
+        if self.win_loss_ratio <= 0.0 {
+            return 0.0;
+        }
+        let kelly = self.win_probability - (1.0 - self.win_probability) / self.win_loss_ratio;
+        (kelly * self.fraction).clamp(0.0, 1.0)
+    }
+}
+
+impl PositionSizer for Kelly {
+    fn size(&self, context: &SizingContext) -> f64 {
+        context.equity * self.kelly_fraction() / context.price
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(asset: &AssetData, atr: Option<f64>) -> SizingContext<'_> {
+        SizingContext { asset, price: 50.0, equity: 1000.0, atr }
+    }
+
+    #[test]
+    fn test_fixed_sizers() {
+        let asset = AssetData::new("BTCUSDT", 1000.0, 0.0, 0.0);
+        assert_eq!(FixedFraction { fraction: 0.5 }.size(&context(&asset, None)), 10.0);
+        assert_eq!(FixedNotional { notional: 250.0 }.size(&context(&asset, None)), 5.0);
+    }
+
+    #[test]
+    fn test_volatility_target() {
+        let asset = AssetData::new("BTCUSDT", 1000.0, 0.0, 0.0);
+        let sizer = VolatilityTarget { target_risk: 0.01, atr_window: 14 };
+        assert_eq!(sizer.size(&context(&asset, Some(2.0))), 5.0);
+        assert_eq!(sizer.size(&context(&asset, None)), 0.0);
+        assert_eq!(sizer.atr_window(), Some(14));
+    }
+
+    #[test]
+    fn test_kelly() {
+        let kelly = Kelly { win_probability: 0.6, win_loss_ratio: 2.0, fraction: 0.5 };
+        assert!((kelly.kelly_fraction() - 0.2).abs() < 1e-12);
+
+        let asset = AssetData::new("BTCUSDT", 1000.0, 0.0, 0.0);
+        assert!((kelly.size(&context(&asset, None)) - 4.0).abs() < 1e-12);
+
+        // A losing edge never sizes a position
+        let losing = Kelly { win_probability: 0.3, win_loss_ratio: 1.0, fraction: 1.0 };
+        assert_eq!(losing.kelly_fraction(), 0.0);
+
+        let estimated = Kelly::from_trade_returns(&[0.1, 0.1, -0.05, 0.1, -0.05], 1.0);
+        assert_eq!(estimated.win_probability, 0.6);
+        assert!((estimated.win_loss_ratio - 2.0).abs() < 1e-12);
+    }
+}
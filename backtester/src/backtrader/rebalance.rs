@@ -0,0 +1,92 @@
+use crate::performance::frequency::{weekday, MS_PER_DAY};
+
+// When the rebalancing engine moves the portfolio back to its target weights
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RebalanceSchedule {
+    Daily,                     // On the first bar of every UTC day
+    Weekly,                    // On the first bar of every week, weeks start on Monday
+    Drift { threshold: f64 },  // Whenever a weight drifts more than `threshold` from its target, 0.05 is 5 points
+}
+
+impl RebalanceSchedule {
+    /// Whether the bar at `timestamp` rebalances, given the timestamp of the previous rebalance
+    /// and the largest absolute distance between a current and a target weight.
+    pub fn is_due(&self, last_rebalance: Option<i64>, timestamp: i64, drift: f64) -> bool {
+        match (self, last_rebalance) {
+            (_, None) => true,
+            (RebalanceSchedule::Daily, Some(last)) => day(last) != day(timestamp),
+            (RebalanceSchedule::Weekly, Some(last)) => week(last) != week(timestamp),
+            (RebalanceSchedule::Drift { threshold }, Some(_)) => drift > *threshold,
+        }
+    }
+}
+
+const fn day(timestamp: i64) -> i64 {
+    timestamp.div_euclid(MS_PER_DAY)
+}
+
+// The day the timestamp's week started on, weeks roll over on Monday
+const fn week(timestamp: i64) -> i64 {
+    day(timestamp) - weekday(timestamp)
+}
+
+/// Signed units to trade per symbol to move `holdings` of (positions, price) to `targets` weights of `equity`.
+/// A weight of None leaves the symbol untouched.
+pub fn rebalance_quantities(holdings: &[(f64, f64)], targets: &[Option<f64>], equity: f64) -> Vec<f64> {
+    holdings
+        .iter()
+        .zip(targets)
+        .map(|(&(positions, price), target)| match target {
+            Some(weight) if price > 0.0 => equity * weight / price - positions,
+            _ => 0.0,
+        })
+        .collect()
+}
+
+// Largest absolute distance between the current weight of a holding and its target
+pub fn weight_drift(holdings: &[(f64, f64)], targets: &[Option<f64>], equity: f64) -> f64 {
+    if equity <= 0.0 {
+        return 0.0;
+    }
+    holdings
+        .iter()
+        .zip(targets)
+        .filter_map(|(&(positions, price), target)| target.map(|weight| (positions * price / equity - weight).abs()))
+        .fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedules() {
+        let hour = MS_PER_DAY / 24;
+        assert!(RebalanceSchedule::Daily.is_due(None, 0, 0.0));
+        assert!(!RebalanceSchedule::Daily.is_due(Some(0), 23 * hour, 0.0));
+        assert!(RebalanceSchedule::Daily.is_due(Some(23 * hour), MS_PER_DAY, 0.0));
+
+        // 1970-01-04 was a Sunday, 1970-01-05 a Monday
+        assert!(!RebalanceSchedule::Weekly.is_due(Some(0), 3 * MS_PER_DAY, 0.0));
+        assert!(RebalanceSchedule::Weekly.is_due(Some(3 * MS_PER_DAY), 4 * MS_PER_DAY, 0.0));
+
+        let drift = RebalanceSchedule::Drift { threshold: 0.05 };
+        assert!(!drift.is_due(Some(0), MS_PER_DAY, 0.04));
+        assert!(drift.is_due(Some(0), MS_PER_DAY, 0.06));
+    }
+
+    #[test]
+    fn test_rebalance_quantities() {
+        // 60/40 drifted to 75/25 after the first asset doubled
+        let holdings = [(6.0, 200.0), (4.0, 100.0)];
+        let targets = [Some(0.6), Some(0.4)];
+        let equity = 1600.0;
+
+        assert!((weight_drift(&holdings, &targets, equity) - 0.15).abs() < 1e-12);
+        let quantities = rebalance_quantities(&holdings, &targets, equity);
+        assert!((quantities[0] - -1.2).abs() < 1e-12);
+        assert!((quantities[1] - 2.4).abs() < 1e-12);
+
+        assert_eq!(rebalance_quantities(&holdings, &[None, Some(0.0)], equity), vec![0.0, -4.0]);
+    }
+}
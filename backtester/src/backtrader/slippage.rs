@@ -0,0 +1,106 @@
+use std::fmt::Debug;
+use crate::backtrader::order::Side;
+use crate::data::candle::Candle;
+
+pub trait SlippageModel: Debug {
+    // Price concession per unit against `price`, positive values make buys pay more and sells receive less
+    fn slippage(&self, side: Side, price: f64, quantity: f64, candle: &Candle) -> f64;
+
+    // Price the order actually fills at
+    fn execution_price(&self, side: Side, price: f64, quantity: f64, candle: &Candle) -> f64 {
+        price + side.direction() * self.slippage(side, price, quantity, candle)
+    }
+}
+
+// Share of the bar's volume the order takes, the whole bar when no volume was reported
+fn participation(quantity: f64, candle: &Candle) -> f64 {
+    if candle.volume > 0.0 {
+        quantity / candle.volume
+    } else {
+        1.0
+    }
+}
+
+// A fixed cost in basis points of the price, 10 bps is 0.1%
+#[derive(Debug, Clone)]
+pub struct FixedBps {
+    pub bps: f64,
+}
+
+impl SlippageModel for FixedBps {
+    fn slippage(&self, _side: Side, price: f64, _quantity: f64, _candle: &Candle) -> f64 {
+        price * self.bps / 10_000.0
+    }
+}
+
+// Cost grows linearly with the share of the bar's volume taken, `impact` is the cost of taking the whole bar
+#[derive(Debug, Clone)]
+pub struct VolumeParticipation {
+    pub impact: f64,
+}
+
+impl SlippageModel for VolumeParticipation {
+    fn slippage(&self, _side: Side, price: f64, quantity: f64, candle: &Candle) -> f64 {
+        price * self.impact * participation(quantity, candle)
+    }
+}
+
+// Square root market impact k * sigma * sqrt(Q / V), with the bar's range over its close as sigma
+#[derive(Debug, Clone)]
+pub struct SquareRootImpact {
+    pub coefficient: f64,
+}
+
+impl SlippageModel for SquareRootImpact {
+    fn slippage(&self, _side: Side, price: f64, quantity: f64, candle: &Candle) -> f64 {
+        let volatility = if candle.close > 0.0 { (candle.high - candle.low) / candle.close } else { 0.0 };
+        price * self.coefficient * volatility * participation(quantity, candle).sqrt()
+    }
+}
+
+// Crossing half of a spread estimated as `fraction` of the bar's high-low range
+#[derive(Debug, Clone)]
+pub struct RangeSpread {
+    pub fraction: f64,
+}
+
+impl SlippageModel for RangeSpread {
+    fn slippage(&self, _side: Side, _price: f64, _quantity: f64, candle: &Candle) -> f64 {
+        self.fraction * (candle.high - candle.low) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CANDLE: Candle = Candle { timestamp: 0, open: 100.0, high: 102.0, low: 98.0, close: 100.0, volume: 100.0 };
+
+    #[test]
+    fn test_fixed_bps() {
+        let model = FixedBps { bps: 10.0 };
+        assert!((model.execution_price(Side::Buy, 100.0, 1.0, &CANDLE) - 100.1).abs() < 1e-12);
+        assert!((model.execution_price(Side::Sell, 100.0, 1.0, &CANDLE) - 99.9).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_volume_models() {
+        // Taking a quarter of the bar
+        let linear = VolumeParticipation { impact: 0.01 };
+        assert!((linear.slippage(Side::Buy, 100.0, 25.0, &CANDLE) - 0.25).abs() < 1e-12);
+
+        // 0.5 * 4% range * sqrt(0.25)
+        let square_root = SquareRootImpact { coefficient: 0.5 };
+        assert!((square_root.slippage(Side::Buy, 100.0, 25.0, &CANDLE) - 1.0).abs() < 1e-12);
+
+        let empty = Candle { volume: 0.0, ..CANDLE };
+        assert!((linear.slippage(Side::Buy, 100.0, 1.0, &empty) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_range_spread() {
+        let model = RangeSpread { fraction: 0.5 };
+        assert_eq!(model.slippage(Side::Sell, 100.0, 1.0, &CANDLE), 1.0);
+        assert_eq!(model.execution_price(Side::Sell, 100.0, 1.0, &CANDLE), 99.0);
+    }
+}
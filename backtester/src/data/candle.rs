@@ -0,0 +1,73 @@
+use polars::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candle {
+    pub timestamp: i64, // Epoch in ms, same unit as the `timestamp` column after `load_csv`
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+// Borrowed column views over an OHLCV frame, so the backtest loop does not pay for AnyValue lookups per cell
+pub struct CandleColumns<'a> {
+    timestamp: &'a DatetimeChunked,
+    open: &'a Float64Chunked,
+    high: &'a Float64Chunked,
+    low: &'a Float64Chunked,
+    close: &'a Float64Chunked,
+    volume: &'a Float64Chunked,
+}
+
+impl<'a> CandleColumns<'a> {
+    pub fn new(df: &'a DataFrame) -> PolarsResult<Self> {
+        Ok(Self {
+            timestamp: df.column("timestamp")?.datetime()?,
+            open: df.column("open")?.f64()?,
+            high: df.column("high")?.f64()?,
+            low: df.column("low")?.f64()?,
+            close: df.column("close")?.f64()?,
+            volume: df.column("volume")?.f64()?,
+        })
+    }
+
+    pub fn get(&self, i: usize) -> Option<Candle> {
+        // Bars with a missing price are skipped by the caller, a missing volume is treated as no volume traded
+        Some(Candle {
+            timestamp: self.timestamp.get(i)?,
+            open: self.open.get(i)?,
+            high: self.high.get(i)?,
+            low: self.low.get(i)?,
+            close: self.close.get(i)?,
+            volume: self.volume.get(i).unwrap_or(0.0),
+        })
+    }
+}
+
+// Frame of candles in the layout `load_csv` produces, timestamps as milliseconds
+pub fn candles_to_dataframe(candles: &[Candle]) -> PolarsResult<DataFrame> {
+    df!(
+        "timestamp" => candles.iter().map(|candle| candle.timestamp).collect::<Vec<_>>(),
+        "open" => candles.iter().map(|candle| candle.open).collect::<Vec<_>>(),
+        "high" => candles.iter().map(|candle| candle.high).collect::<Vec<_>>(),
+        "low" => candles.iter().map(|candle| candle.low).collect::<Vec<_>>(),
+        "close" => candles.iter().map(|candle| candle.close).collect::<Vec<_>>(),
+        "volume" => candles.iter().map(|candle| candle.volume).collect::<Vec<_>>(),
+    )?
+        .lazy()
+        .with_column(col("timestamp").cast(DataType::Datetime(TimeUnit::Milliseconds, None)))
+        .collect()
+}
+
+// Every complete candle of an OHLCV frame, in row order
+pub fn dataframe_to_candles(df: &DataFrame) -> PolarsResult<Vec<Candle>> {
+    let columns = CandleColumns::new(df)?;
+    Ok((0..df.height()).filter_map(|i| columns.get(i)).collect())
+}
+
+// Candle at the epoch with a volume of 10, what the unit tests of fills and exits trade against
+#[cfg(test)]
+pub(crate) const fn candle(open: f64, high: f64, low: f64, close: f64) -> Candle {
+    Candle { timestamp: 0, open, high, low, close, volume: 10.0 }
+}
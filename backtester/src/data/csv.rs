@@ -1,52 +1,106 @@
-use std::sync::Arc;
-use polars::frame::DataFrame;
-use polars::io::SerReader;
-use polars::prelude::{col, lit, CsvReadOptions, DataType, IntoLazy, TimeUnit};
-
-pub fn load_csv(file_path: &str) -> DataFrame {
-    // Open and read the CSV file
-    let dtype_overwrite = Some(Arc::new(vec![
-        DataType::String,
-         DataType::Float64,
-         DataType::Float64,
-         DataType::Float64,
-         DataType::Float64,
-         DataType::Float64
-    ]));
-
-
-    let reader_options = CsvReadOptions {
-        has_header: true,
-        dtype_overwrite,
-        infer_schema_length:Some(10000),
-        //infer_schema_length: Some(1000000000),
-        ..Default::default()
+use polars::prelude::*;
+use crate::data::source::DateRange;
+
+const PRICE_COLUMNS: [&str; 5] = ["open", "high", "low", "close", "volume"];
+
+/// Lazily scan an OHLCV CSV, nothing is read until the frame is collected.
+/// Column selections on the result are pushed down into the reader, so only the needed columns are read.
+pub fn scan_csv(file_path: &str) -> PolarsResult<LazyFrame> {
+    scan_csv_between(file_path, DateRange::default())
+}
+
+/// Like `scan_csv` but only rows within `window` are kept, the filter runs inside the reader
+/// so a file larger than memory can be read a slice of time at a time.
+pub fn scan_csv_between(file_path: &str, window: DateRange) -> PolarsResult<LazyFrame> {
+    // Prices and volumes are read as floats whatever the first rows look like, an integer volume
+    // would otherwise be inferred from them and fail to parse at the first fractional one
+    let mut lazy = LazyCsvReader::new(file_path)
+        .with_has_header(true)
+        .with_infer_schema_length(Some(10000))
+        .with_schema_modify(|schema| {
+            Ok(schema
+                .iter()
+                .map(|(name, dtype)| {
+                    let price = PRICE_COLUMNS.contains(&name.to_lowercase().as_str());
+                    Field::new(name.clone(), if price { DataType::Float64 } else { dtype.clone() })
+                })
+                .collect())
+        })?
+        .finish()?;
+
+    let names: Vec<String> = lazy.collect_schema()?.iter_names().map(|name| name.to_string()).collect();
+    let lowercased_cols: Vec<String> = names.iter().map(|name| name.to_lowercase()).collect();
+    let Some(timestamp) = names.iter().find(|name| name.eq_ignore_ascii_case("timestamp")) else {
+        return Err(PolarsError::ColumnNotFound(format!("'{}' has no timestamp column", file_path).into()));
     };
+    // Filtering before the columns are renamed and converted lets polars push the predicate into the scan
+    if let Some(predicate) = window.predicate_on(to_datetime(col(timestamp.as_str()))) {
+        lazy = lazy.filter(predicate);
+    }
+
+    Ok(lazy
+        .rename(&names, &lowercased_cols, true)
+        .with_column(to_datetime(col("timestamp"))))
+}
+
+fn to_datetime(timestamp: Expr) -> Expr {
+    (timestamp.cast(DataType::Float64) * lit(1_000)) // Change from epoc in seconds to epoc in ms
+        .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
+}
+
+// Read the whole CSV into memory, prefer `scan_csv` for large files
+pub fn load_csv(file_path: &str) -> PolarsResult<DataFrame> {
+    scan_csv(file_path)?.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_csv() {
+        let path = std::env::temp_dir().join("scan_csv_test.csv");
+        std::fs::write(
+            &path,
+            "Timestamp,Open,High,Low,Close,Volume\n60,1.0,2.0,0.5,1.5,10\n120,1.5,2.5,1.0,2.0,20\n180,2.0,3.0,1.5,2.5,30\n",
+        ).unwrap();
+
+        let data = scan_csv(path.to_str().unwrap())
+            .unwrap()
+            .filter(col("timestamp").gt(lit(60_000).cast(DataType::Datetime(TimeUnit::Milliseconds, None))))
+            .select([col("timestamp"), col("volume")])
+            .collect()
+            .unwrap();
+        assert_eq!(data.shape(), (2, 2));
+        assert_eq!(data.column("volume").unwrap().dtype(), &DataType::Float64);
+        assert_eq!(data.column("timestamp").unwrap().cast(&DataType::Int64).unwrap().i64().unwrap().get(0), Some(120_000));
+
+        let window = DateRange { start: Some(120_000), end: Some(180_000) };
+        let lazy = scan_csv_between(path.to_str().unwrap(), window).unwrap();
+        assert!(lazy.explain(true).unwrap().contains("SELECTION"));
+        assert_eq!(lazy.collect().unwrap().height(), 1);
+
+        let data = load_csv(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(data.get_column_names(), ["timestamp", "open", "high", "low", "close", "volume"]);
+        assert!(load_csv("missing.csv").is_err());
+    }
+
+    #[test]
+    fn test_fractional_volume_after_integer_rows() {
+        let path = std::env::temp_dir().join("scan_csv_volume_test.csv");
+        let mut csv = String::from("Timestamp,Open,High,Low,Close,Volume\n");
+        // Whole numbers for longer than the schema is inferred from
+        for i in 0..10_001 {
+            csv.push_str(&format!("{},1,2,1,2,10\n", i * 60));
+        }
+        csv.push_str("600060,1.5,2.5,1.0,2.0,0.25\n");
+        std::fs::write(&path, csv).unwrap();
 
-    let mut df = reader_options
-        .try_into_reader_with_file_path(Some(file_path.into()))
-        .unwrap()
-        .finish()
-        .unwrap();
-
-    let lowercased_cols: Vec<String> = df
-        .get_column_names()
-        .iter()
-        .map(|name| name.to_lowercase())
-        .collect();
-
-    df.set_column_names(&lowercased_cols)
-        .expect("Failed to set column names to lowercase");
-
-    df = df
-        .lazy()
-        .with_columns([
-            (col("timestamp").cast(DataType::Float64) * lit(1_000)) // Change from epoc in seconds to epoc in ms
-                .cast(DataType::Datetime(TimeUnit::Milliseconds, None)),
-        ]
-        )
-        .collect()
-        .unwrap();
-
-    df
-}
\ No newline at end of file
+        let data = load_csv(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(data.height(), 10_002);
+        assert_eq!(data.column("open").unwrap().dtype(), &DataType::Float64);
+        assert_eq!(data.column("volume").unwrap().f64().unwrap().get(10_001), Some(0.25));
+    }
+}
@@ -1,23 +1,47 @@
-use polars::prelude::DataFrame;
+use polars::prelude::{DataFrame, PolarsResult};
 use crate::data::csv::load_csv;
+use crate::data::source::{DataSource, DateRange};
 
 pub trait DataHandlerFetch<T> where T: Into<&'static str> {
-    fn load_data(options: T) -> DataFrame;
+    fn load_data(options: T) -> PolarsResult<DataFrame>;
 }
 
-#[allow(dead_code)]
 pub struct DataHandler {
     symbol: String,
     start_date: String,
     end_date: String,
 }
 
+impl DataHandler {
+    // Dates are "YYYY-MM-DD" in UTC, both days are included
+    pub fn new(symbol: &str, start_date: &str, end_date: &str) -> Self {
+        DataHandler {
+            symbol: symbol.to_string(),
+            start_date: start_date.to_string(),
+            end_date: end_date.to_string(),
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn date_range(&self) -> PolarsResult<DateRange> {
+        DateRange::parse(Some(&self.start_date), Some(&self.end_date))
+    }
+
+    // Only the candles between the handler's dates are read from the file
+    pub fn load(&self, file_path: &str) -> PolarsResult<DataFrame> {
+        DataSource::new(file_path).load_between(self.date_range()?)
+    }
+}
+
 impl<T> DataHandlerFetch<T> for DataHandler
 where
     T: Into<&'static str>,
 {
-    fn load_data(options: T) -> DataFrame {
+    fn load_data(options: T) -> PolarsResult<DataFrame> {
         let filename: &str = options.into();
-        load_csv(filename) // This assumes `load_csv` is defined and returns `DataFrame`.
+        load_csv(filename)
     }
-}
\ No newline at end of file
+}
@@ -1,2 +1,7 @@
 pub mod csv;
-pub mod data;
\ No newline at end of file
+pub mod parquet;
+#[allow(clippy::module_inception)]
+pub mod data;
+pub mod candle;
+pub mod stream;
+pub mod source;
\ No newline at end of file
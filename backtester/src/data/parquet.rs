@@ -0,0 +1,37 @@
+use polars::prelude::*;
+use crate::data::source::DateRange;
+
+/// Lazily scan an OHLCV parquet file into the layout `scan_csv` produces, numeric timestamps are epoch seconds like in the CSVs.
+/// Only rows within `window` are kept, the filter runs inside the reader like in `scan_csv_between`.
+pub fn scan_parquet_between(file_path: &str, window: DateRange) -> PolarsResult<LazyFrame> {
+    let mut lazy = LazyFrame::scan_parquet(file_path, ScanArgsParquet::default())?;
+
+    let schema = lazy.collect_schema()?;
+    let names: Vec<String> = schema.iter_names().map(|name| name.to_string()).collect();
+    let lowercased_cols: Vec<String> = names.iter().map(|name| name.to_lowercase()).collect();
+    let Some(timestamp) = names.iter().find(|name| name.eq_ignore_ascii_case("timestamp")) else {
+        return Err(PolarsError::ColumnNotFound(format!("'{}' has no timestamp column", file_path).into()));
+    };
+    let is_datetime = matches!(schema.get(timestamp.as_str()), Some(DataType::Datetime(_, _)));
+    if let Some(predicate) = window.predicate_on(to_datetime(col(timestamp.as_str()), is_datetime)) {
+        lazy = lazy.filter(predicate);
+    }
+
+    Ok(lazy
+        .rename(&names, &lowercased_cols, true)
+        .with_column(to_datetime(col("timestamp"), is_datetime)))
+}
+
+fn to_datetime(timestamp: Expr, is_datetime: bool) -> Expr {
+    let datetime = DataType::Datetime(TimeUnit::Milliseconds, None);
+    if is_datetime {
+        timestamp.cast(datetime)
+    } else {
+        (timestamp.cast(DataType::Float64) * lit(1_000)).cast(datetime)
+    }
+}
+
+// Read the whole parquet file into memory
+pub fn load_parquet(file_path: &str) -> PolarsResult<DataFrame> {
+    scan_parquet_between(file_path, DateRange::default())?.collect()
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::path::Path;
+use polars::export::chrono::{Days, NaiveDate, NaiveDateTime};
+use polars::prelude::*;
+use serde::Deserialize;
+use crate::data::csv::scan_csv_between;
+use crate::data::parquet::scan_parquet_between;
+use crate::performance::frequency::BarFrequency;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataFormat {
+    Csv,
+    Parquet,
+}
+
+/// Where a symbol's candles are loaded from and what they are expected to be.
+/// Dates are "YYYY-MM-DD" or "YYYY-MM-DD HH:MM:SS" in UTC, a bare end date includes that whole day.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DataSource {
+    pub path: String,
+    #[serde(default)]
+    pub format: Option<DataFormat>, // Taken from the file extension when not given
+    #[serde(default)]
+    pub exchange: Option<String>, // Exchange the candles were recorded on, it has to be the one the backtest trades on
+    #[serde(default)]
+    pub timeframe: Option<String>, // Bar spacing such as "2min" or "1h", checked against the loaded bars
+    #[serde(default)]
+    pub start_date: Option<String>,
+    #[serde(default)]
+    pub end_date: Option<String>,
+}
+
+impl DataSource {
+    pub fn new(path: &str) -> Self {
+        DataSource {
+            path: path.to_string(),
+            format: None,
+            exchange: None,
+            timeframe: None,
+            start_date: None,
+            end_date: None,
+        }
+    }
+
+    // Sources keyed by symbol from a JSON file, see `examples/config/data_sources.json`
+    pub fn from_file(path: &str) -> PolarsResult<HashMap<String, DataSource>> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|error| PolarsError::ComputeError(format!("Could not read data source config '{}': {}", path, error).into()))?;
+        serde_json::from_str(&config)
+            .map_err(|error| PolarsError::ComputeError(format!("Invalid data source config '{}': {}", path, error).into()))
+    }
+
+    pub fn format(&self) -> PolarsResult<DataFormat> {
+        if let Some(format) = self.format {
+            return Ok(format);
+        }
+        match Path::new(&self.path).extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => Ok(DataFormat::Csv),
+            Some(extension) if extension.eq_ignore_ascii_case("parquet") => Ok(DataFormat::Parquet),
+            _ => Err(PolarsError::ComputeError(format!("Cannot tell the format of '{}', set it on the data source", self.path).into())),
+        }
+    }
+
+    pub fn date_range(&self) -> PolarsResult<DateRange> {
+        DateRange::parse(self.start_date.as_deref(), self.end_date.as_deref())
+    }
+
+    pub fn load(&self) -> PolarsResult<DataFrame> {
+        self.load_between(DateRange::default())
+    }
+
+    /// Read the candles within both the source's date range and `window`, the filter runs while the file is scanned.
+    /// Fails when the bars are spaced differently from the configured timeframe.
+    pub fn load_between(&self, window: DateRange) -> PolarsResult<DataFrame> {
+        let window = self.date_range()?.intersect(window);
+        let data = match self.format()? {
+            DataFormat::Csv => scan_csv_between(&self.path, window)?.collect()?,
+            DataFormat::Parquet => scan_parquet_between(&self.path, window)?.collect()?,
+        };
+
+        self.check_timeframe(&data)?;
+        Ok(data)
+    }
+
+    fn check_timeframe(&self, data: &DataFrame) -> PolarsResult<()> {
+        let Some(timeframe) = &self.timeframe else {
+            return Ok(());
+        };
+        let expected: BarFrequency = timeframe.parse()?;
+        let timestamps: Vec<i64> = data.column("timestamp")?.cast(&DataType::Int64)?.i64()?.into_iter().flatten().collect();
+        match BarFrequency::detect(&timestamps) {
+            Some(detected) if detected != expected => Err(PolarsError::ComputeError(
+                format!("'{}' has {} bars, the data source expects {}", self.path, detected, expected).into(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Start (inclusive) and end (exclusive) of a time window in epoch ms, unbounded on a missing side.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+}
+
+impl DateRange {
+    // Dates as in `DataSource`, a bare end date includes that whole day
+    pub fn parse(start_date: Option<&str>, end_date: Option<&str>) -> PolarsResult<Self> {
+        let start = start_date.map(|date| parse_date(date, false)).transpose()?;
+        let end = end_date.map(|date| parse_date(date, true)).transpose()?;
+        if let (Some(start), Some(end)) = (start, end) {
+            if start >= end {
+                return Err(PolarsError::ComputeError(format!("Date range ends before it starts: {:?} to {:?}", start_date, end_date).into()));
+            }
+        }
+        Ok(DateRange { start, end })
+    }
+
+    // The part of the time both ranges cover
+    pub fn intersect(self, other: DateRange) -> Self {
+        let end = match (self.end, other.end) {
+            (Some(end), Some(other_end)) => Some(end.min(other_end)),
+            (end, other_end) => end.or(other_end),
+        };
+        DateRange { start: self.start.max(other.start), end }
+    }
+
+    pub fn contains(&self, timestamp: i64) -> bool {
+        self.start.is_none_or(|start| timestamp >= start) && self.end.is_none_or(|end| timestamp < end)
+    }
+
+    // Filter on the `timestamp` column, None when the range is unbounded
+    pub fn predicate(&self) -> Option<Expr> {
+        self.predicate_on(col("timestamp"))
+    }
+
+    // Filter on a millisecond datetime expression
+    pub fn predicate_on(&self, timestamp: Expr) -> Option<Expr> {
+        let datetime = DataType::Datetime(TimeUnit::Milliseconds, None);
+        let start = self.start.map(|start| timestamp.clone().gt_eq(lit(start).cast(datetime.clone())));
+        let end = self.end.map(|end| timestamp.lt(lit(end).cast(datetime)));
+        match (start, end) {
+            (Some(start), Some(end)) => Some(start.and(end)),
+            (start, end) => start.or(end),
+        }
+    }
+}
+
+fn parse_date(value: &str, end: bool) -> PolarsResult<i64> {
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Ok(datetime.and_utc().timestamp_millis());
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| PolarsError::ComputeError(format!("Invalid date '{}', expected YYYY-MM-DD", value).into()))?;
+    // An end date runs until the start of the next day
+    let date = if end { date.checked_add_days(Days::new(1)).unwrap_or(date) } else { date };
+    Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use crate::data::candle::{candles_to_dataframe, Candle};
+    use crate::performance::frequency::MS_PER_DAY;
+
+    #[test]
+    fn test_date_range() {
+        let source = DataSource {
+            start_date: Some("2023-01-01".into()),
+            end_date: Some("2023-01-31".into()),
+            ..DataSource::new("btc.csv")
+        };
+        let range = source.date_range().unwrap();
+        assert_eq!(range.start, Some(1_672_531_200_000));
+        assert_eq!(range.end, Some(1_672_531_200_000 + 31 * MS_PER_DAY));
+
+        let source = DataSource { start_date: Some("2023-01-01 12:00:00".into()), ..DataSource::new("btc.csv") };
+        assert_eq!(source.date_range().unwrap().start, Some(1_672_531_200_000 + MS_PER_DAY / 2));
+        let source = DataSource { end_date: Some("01/02/2023".into()), ..DataSource::new("btc.csv") };
+        assert!(source.date_range().is_err());
+        assert!(DateRange::parse(Some("2023-02-01"), Some("2023-01-01")).is_err());
+
+        let window = DateRange::parse(Some("2023-01-15"), None).unwrap();
+        assert_eq!(range.intersect(window), DateRange { start: window.start, end: range.end });
+        assert_eq!(DateRange::default().intersect(window), window);
+        assert!(DateRange::default().predicate().is_none());
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(DataSource::new("btc.CSV").format().unwrap(), DataFormat::Csv);
+        assert_eq!(DataSource::new("btc.parquet").format().unwrap(), DataFormat::Parquet);
+        assert!(DataSource::new("btc").format().is_err());
+    }
+
+    #[test]
+    fn test_load_filters_and_checks_timeframe() {
+        let candles: Vec<Candle> = (0..5)
+            .map(|day| Candle { timestamp: day * MS_PER_DAY, open: 1.0, high: 1.0, low: 1.0, close: 1.0, volume: 1.0 })
+            .collect();
+        let path = std::env::temp_dir().join("data_source_test.parquet");
+        ParquetWriter::new(File::create(&path).unwrap()).finish(&mut candles_to_dataframe(&candles).unwrap()).unwrap();
+
+        let mut source = DataSource {
+            timeframe: Some("1d".into()),
+            start_date: Some("1970-01-02".into()),
+            end_date: Some("1970-01-03".into()),
+            ..DataSource::new(path.to_str().unwrap())
+        };
+        let data = source.load().unwrap();
+        assert_eq!(data.height(), 2);
+        let window = DateRange::parse(None, Some("1970-01-02")).unwrap();
+        assert_eq!(source.load_between(window).unwrap().height(), 1);
+        assert!(scan_parquet_between(&source.path, window).unwrap().explain(true).unwrap().contains("SELECTION"));
+
+        source.timeframe = Some("1h".into());
+        assert!(source.load().is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_from_json() {
+        let sources: HashMap<String, DataSource> = serde_json::from_str(
+            r#"{"BTCUSDT": {"path": "btc.parquet", "exchange": "binance", "timeframe": "1min"}}"#,
+        ).unwrap();
+        assert_eq!(sources["BTCUSDT"].exchange.as_deref(), Some("binance"));
+        assert_eq!(sources["BTCUSDT"].start_date, None);
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::stream::{self, Stream};
+use polars::prelude::*;
+use crate::data::candle::{dataframe_to_candles, Candle};
+use crate::data::csv::scan_csv;
+use crate::data::parquet::scan_parquet_between;
+use crate::data::source::DateRange;
+
+// Rows a replay reads from its scan at a time, only one slice of the file is held in memory
+const REPLAY_SLICE_ROWS: IdxSize = 65_536;
+
+/// Candles one bar at a time, replayed from history or pushed by a live feed.
+/// Backtests and live trading consume the same stream, so a strategy runs the same code path in both.
+pub struct CandleStream {
+    inner: Pin<Box<dyn Stream<Item = Candle> + Send>>,
+}
+
+impl CandleStream {
+    // Any stream of candles, e.g. a websocket feed mapped to candles
+    pub fn live(feed: impl Stream<Item = Candle> + Send + 'static) -> Self {
+        Self { inner: Box::pin(feed) }
+    }
+
+    // A stream fed by pushing candles into the sender, it ends once every sender is dropped
+    pub fn channel() -> (UnboundedSender<Candle>, Self) {
+        let (sender, receiver) = unbounded();
+        (sender, Self::live(receiver))
+    }
+
+    // Replay the complete candles of an OHLCV frame in row order
+    pub fn replay(df: &DataFrame) -> PolarsResult<Self> {
+        Self::replay_scan(df.clone().lazy())
+    }
+
+    // Replay the complete candles of a lazy OHLCV scan in row order, the next slice is read once the previous one was streamed.
+    // The first slice is read right away, so a missing file or column is reported here instead of ending the stream.
+    pub fn replay_scan(scan: LazyFrame) -> PolarsResult<Self> {
+        let read = move |offset: i64| -> PolarsResult<(IdxSize, Vec<Candle>)> {
+            let slice = scan.clone().slice(offset, REPLAY_SLICE_ROWS).collect()?;
+            Ok((slice.height() as IdxSize, dataframe_to_candles(&slice)?))
+        };
+        let (mut rows, first) = read(0)?;
+        let mut offset = rows as i64;
+        let rest = std::iter::from_fn(move || {
+            if rows < REPLAY_SLICE_ROWS {
+                return None;
+            }
+            match read(offset) {
+                Ok((read_rows, candles)) => {
+                    rows = read_rows;
+                    offset += rows as i64;
+                    Some(candles)
+                }
+                Err(error) => {
+                    eprintln!("Replay stopped at row {}: {}", offset, error);
+                    None
+                }
+            }
+        });
+        Ok(Self::live(stream::iter(std::iter::once(first).chain(rest).flatten())))
+    }
+
+    pub fn from_csv(file_path: &str) -> PolarsResult<Self> {
+        Self::replay_scan(scan_csv(file_path)?)
+    }
+
+    pub fn from_parquet(file_path: &str) -> PolarsResult<Self> {
+        Self::replay_scan(scan_parquet_between(file_path, DateRange::default())?)
+    }
+}
+
+impl Stream for CandleStream {
+    type Item = Candle;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Candle>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+impl fmt::Debug for CandleStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CandleStream").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use crate::backtrader::asset_data::AssetData;
+    use crate::data::candle::{candles_to_dataframe, dataframe_to_candles};
+
+    fn candle(timestamp: i64, close: f64) -> Candle {
+        Candle { timestamp, open: close, high: close, low: close, close, volume: 1.0 }
+    }
+
+    #[test]
+    fn test_replay() {
+        let df = candles_to_dataframe(&[candle(0, 100.0), candle(60_000, 101.0)]).unwrap();
+        let candles: Vec<Candle> = block_on(CandleStream::replay(&df).unwrap().collect());
+        assert_eq!(candles, vec![candle(0, 100.0), candle(60_000, 101.0)]);
+
+        // Longer than one slice, the next one continues where the previous one stopped
+        let rows = REPLAY_SLICE_ROWS as i64 + 10;
+        let long: Vec<Candle> = (0..rows).map(|i| candle(i * 60_000, 100.0)).collect();
+        let candles: Vec<Candle> = block_on(CandleStream::replay(&candles_to_dataframe(&long).unwrap()).unwrap().collect());
+        assert_eq!(candles, long);
+    }
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let mut df = candles_to_dataframe(&[candle(0, 100.0), candle(60_000, 101.0)]).unwrap();
+        let path = std::env::temp_dir().join("candle_stream_test.parquet");
+        ParquetWriter::new(File::create(&path).unwrap()).finish(&mut df).unwrap();
+
+        let candles: Vec<Candle> = block_on(CandleStream::from_parquet(path.to_str().unwrap()).unwrap().collect());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[1], candle(60_000, 101.0));
+    }
+
+    #[test]
+    fn test_channel() {
+        let (sender, mut stream) = CandleStream::channel();
+        sender.unbounded_send(candle(0, 100.0)).unwrap();
+        drop(sender);
+        assert_eq!(block_on(stream.next()), Some(candle(0, 100.0)));
+        assert_eq!(block_on(stream.next()), None);
+    }
+
+    #[test]
+    fn test_long_stream_keeps_few_chunks() {
+        let candles: Vec<Candle> = (0..10_000).map(|i| candle(i * 60_000, 100.0)).collect();
+        let mut stream = CandleStream::replay(&candles_to_dataframe(&candles).unwrap()).unwrap();
+        let mut asset = AssetData::new("BTCUSDT", 1000.0, 0.0, 0.0);
+        let mut streamed = 0;
+        while block_on(asset.load_latest_candle(&mut stream)).unwrap().is_some() {
+            streamed += 1;
+            // Appended in batches, merged whenever they doubled the data
+            let chunks = asset.get_data().as_ref().map_or(0, |data| data.first_col_n_chunks());
+            assert!(chunks <= streamed / 1024 + 1);
+        }
+
+        let data = asset.get_data().as_ref().unwrap();
+        assert_eq!(data.height(), 10_000);
+        assert!(data.first_col_n_chunks() <= 4);
+        assert_eq!(dataframe_to_candles(data).unwrap(), candles);
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(non_snake_case)] // The crate is published as `Backtester`
+
 pub mod strategy;
 pub mod performance;
 pub mod backtrader;
@@ -0,0 +1,208 @@
+use std::fmt;
+use polars::prelude::*;
+use crate::data::csv::load_csv;
+
+// Tracking errors below this are rounding noise of a strategy that replicates the benchmark
+const TRACKING_DUST: f64 = 1e-12;
+
+// What a strategy is measured against
+#[derive(Debug, Clone, PartialEq)]
+pub enum Benchmark {
+    BuyAndHold,                // Holding every traded asset from the first bar with its share of the capital
+    Prices(Vec<(i64, f64)>),   // Timestamped prices of an external series, e.g. an index or another asset
+}
+
+impl Benchmark {
+    // Close prices of a frame with `timestamp` and `close` columns
+    pub fn from_dataframe(df: &DataFrame) -> PolarsResult<Self> {
+        Ok(Benchmark::Prices(close_prices(df)?))
+    }
+
+    pub fn from_csv(file_path: &str) -> PolarsResult<Self> {
+        Self::from_dataframe(&load_csv(file_path)?)
+    }
+}
+
+pub fn close_prices(df: &DataFrame) -> PolarsResult<Vec<(i64, f64)>> {
+    let timestamps = df.column("timestamp")?.cast(&DataType::Int64)?;
+    let closes = df.column("close")?.cast(&DataType::Float64)?;
+    Ok(timestamps
+        .i64()?
+        .into_iter()
+        .zip(closes.f64()?)
+        .filter_map(|(timestamp, close)| Some((timestamp?, close?)))
+        .collect())
+}
+
+// Value of `capital` put into the benchmark at its first price
+pub fn benchmark_equity(prices: &[(i64, f64)], capital: f64) -> Vec<(i64, f64)> {
+    let Some(&(_, first)) = prices.first() else {
+        return vec![];
+    };
+    prices.iter().map(|&(timestamp, price)| (timestamp, capital * price / first)).collect()
+}
+
+// Benchmark price at every equity timestamp, the last known price carries over gaps and the first one fills the start
+fn align(equity: &[(i64, f64)], prices: &[(i64, f64)]) -> Vec<f64> {
+    let mut cursor = 0;
+    let mut last = prices.first().map_or(f64::NAN, |point| point.1);
+    equity
+        .iter()
+        .map(|&(timestamp, _)| {
+            while cursor < prices.len() && prices[cursor].0 <= timestamp {
+                last = prices[cursor].1;
+                cursor += 1;
+            }
+            last
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkMetrics {
+    pub alpha: f64, // Annualized return not explained by the benchmark exposure
+    pub beta: f64,
+    pub tracking_error: f64, // Annualized volatility of the excess returns
+    pub information_ratio: f64,
+    pub up_capture: f64,   // Share of the benchmark's gains captured in periods it rose
+    pub down_capture: f64, // Share of the benchmark's losses taken in periods it fell, below 1 is better
+}
+
+impl BenchmarkMetrics {
+    /// Compare an equity curve to benchmark prices sampled on the equity's timestamps.
+    /// `periods_per_year` is the number of bars in a year, the same one used for the other metrics.
+    pub fn calculate(
+        equity: &[(i64, f64)],
+        benchmark: &[(i64, f64)],
+        periods_per_year: f64,
+        risk_free_rate: f64,
+    ) -> PolarsResult<Self> {
+        let values: Vec<f64> = equity.iter().map(|point| point.1).collect();
+        let returns = period_returns(&values);
+        let benchmark_returns = period_returns(&align(equity, benchmark));
+
+        let pairs: Vec<(f64, f64)> = returns
+            .into_iter()
+            .zip(benchmark_returns)
+            .filter(|(r, b)| r.is_finite() && b.is_finite())
+            .collect();
+        if pairs.len() < 2 {
+            return Ok(Self { alpha: 0.0, beta: 0.0, tracking_error: 0.0, information_ratio: 0.0, up_capture: 0.0, down_capture: 0.0 });
+        }
+
+        let n = pairs.len() as f64;
+        let mean_return = pairs.iter().map(|pair| pair.0).sum::<f64>() / n;
+        let mean_benchmark = pairs.iter().map(|pair| pair.1).sum::<f64>() / n;
+        let covariance = pairs.iter().map(|(r, b)| (r - mean_return) * (b - mean_benchmark)).sum::<f64>() / (n - 1.0);
+        let variance = pairs.iter().map(|(_, b)| (b - mean_benchmark).powi(2)).sum::<f64>() / (n - 1.0);
+        let beta = if variance > 0.0 { covariance / variance } else { 0.0 };
+
+        // Jensen's alpha on per period returns, annualized like the returns
+        let risk_free = risk_free_rate / periods_per_year;
+        let alpha = (mean_return - risk_free - beta * (mean_benchmark - risk_free)) * periods_per_year;
+
+        let mean_excess = mean_return - mean_benchmark;
+        let excess_variance = pairs.iter().map(|(r, b)| (r - b - mean_excess).powi(2)).sum::<f64>() / (n - 1.0);
+        let tracking_error = excess_variance.sqrt() * periods_per_year.sqrt();
+        let information_ratio = if tracking_error > TRACKING_DUST { mean_excess * periods_per_year / tracking_error } else { 0.0 };
+
+        Ok(Self {
+            alpha,
+            beta,
+            tracking_error,
+            information_ratio,
+            up_capture: capture(&pairs, |b| b > 0.0),
+            down_capture: capture(&pairs, |b| b < 0.0),
+        })
+    }
+}
+
+// Return of every period, NaN ones are kept so the strategy and benchmark returns stay paired by position
+fn period_returns(values: &[f64]) -> Vec<f64> {
+    values.windows(2).map(|pair| pair[1] / pair[0] - 1.0).collect()
+}
+
+// Mean strategy return over mean benchmark return in the periods the benchmark matches `filter`
+fn capture(pairs: &[(f64, f64)], filter: impl Fn(f64) -> bool) -> f64 {
+    let selected: Vec<&(f64, f64)> = pairs.iter().filter(|(_, b)| filter(*b)).collect();
+    let benchmark: f64 = selected.iter().map(|pair| pair.1).sum();
+    if selected.is_empty() || benchmark == 0.0 {
+        return 0.0;
+    }
+    selected.iter().map(|pair| pair.0).sum::<f64>() / benchmark
+}
+
+impl fmt::Display for BenchmarkMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Alpha: {:.2}%", self.alpha * 100.0)?;
+        writeln!(f, "Beta: {:.2}", self.beta)?;
+        writeln!(f, "Tracking Error: {:.2}%", self.tracking_error * 100.0)?;
+        writeln!(f, "Information Ratio: {:.2}", self.information_ratio)?;
+        writeln!(f, "Up Capture: {:.2}%", self.up_capture * 100.0)?;
+        writeln!(f, "Down Capture: {:.2}%", self.down_capture * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::frequency::{annualization_curve, MS_PER_HOUR};
+
+    #[test]
+    fn test_leveraged_copy_of_benchmark() {
+        // Twice the benchmark's returns every period
+        let benchmark = [(0, 100.0), (1, 110.0), (2, 99.0), (3, 108.9)];
+        let equity = [(0, 1000.0), (1, 1200.0), (2, 960.0), (3, 1152.0)];
+        let metrics = BenchmarkMetrics::calculate(&equity, &benchmark, 365.0, 0.0).unwrap();
+
+        assert!((metrics.beta - 2.0).abs() < 1e-9);
+        assert!(metrics.alpha.abs() < 1e-9);
+        assert!((metrics.up_capture - 2.0).abs() < 1e-9);
+        assert!((metrics.down_capture - 2.0).abs() < 1e-9);
+        assert!(metrics.tracking_error > 0.0);
+    }
+
+    #[test]
+    fn test_identical_to_benchmark() {
+        let prices = [(0, 100.0), (1, 110.0), (2, 99.0), (3, 108.9)];
+        let equity = benchmark_equity(&prices, 1000.0);
+        let metrics = BenchmarkMetrics::calculate(&equity, &prices, 365.0, 0.0).unwrap();
+
+        assert!((metrics.beta - 1.0).abs() < 1e-9);
+        assert!(metrics.tracking_error.abs() < 1e-9);
+        assert_eq!(metrics.information_ratio, 0.0);
+    }
+
+    #[test]
+    fn test_identical_hourly_benchmark_on_daily_curve() {
+        // Intraday equity is resampled to daily closes, the benchmark stays hourly
+        let prices: Vec<(i64, f64)> = (0..72).map(|hour| (hour * MS_PER_HOUR, 100.0 + (hour % 7) as f64 * (hour / 24 + 1) as f64)).collect();
+        let (curve, periods_per_year) = annualization_curve(&benchmark_equity(&prices, 1000.0));
+        assert_eq!(curve.len(), 3);
+        let metrics = BenchmarkMetrics::calculate(&curve, &prices, periods_per_year, 0.0).unwrap();
+
+        assert!((metrics.beta - 1.0).abs() < 1e-9);
+        assert!(metrics.tracking_error.abs() < 1e-9);
+        assert!(metrics.alpha.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_undefined_return_keeps_periods_paired() {
+        // Returns of 10%, -10%, 20%, -20% and 10%, the equity misses its second value
+        let prices = [(0, 100.0), (1, 110.0), (2, 99.0), (3, 118.8), (4, 95.04), (5, 104.544)];
+        let mut equity = benchmark_equity(&prices, 1000.0);
+        equity[1].1 = f64::NAN;
+        let metrics = BenchmarkMetrics::calculate(&equity, &prices, 365.0, 0.0).unwrap();
+
+        // Only the periods both have a return for are compared, and those match
+        assert!((metrics.beta - 1.0).abs() < 1e-9);
+        assert!(metrics.tracking_error.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_align_carries_last_price() {
+        let prices = [(10, 100.0), (30, 120.0)];
+        let equity = [(0, 1.0), (10, 1.0), (20, 1.0), (30, 1.0)];
+        assert_eq!(align(&equity, &prices), vec![100.0, 100.0, 100.0, 120.0]);
+    }
+}
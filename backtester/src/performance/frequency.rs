@@ -0,0 +1,169 @@
+use std::fmt;
+use std::str::FromStr;
+use polars::prelude::PolarsError;
+
+pub const MS_PER_MINUTE: i64 = 60 * 1000;
+pub const MS_PER_HOUR: i64 = 60 * MS_PER_MINUTE;
+pub const MS_PER_DAY: i64 = 24 * MS_PER_HOUR;
+pub const DAYS_PER_YEAR: f64 = 365.0; // Crypto trades every day of the year
+
+// Day of the week of a UTC timestamp, Monday is 0 and Sunday 6
+pub const fn weekday(timestamp: i64) -> i64 {
+    // The epoch was a Thursday, shift by three days so weeks start on Monday
+    (timestamp.div_euclid(MS_PER_DAY) + 3).rem_euclid(7)
+}
+
+// Spacing of the bars in an equity curve or price series
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarFrequency {
+    pub interval_ms: i64,
+}
+
+impl BarFrequency {
+    /// Median spacing between consecutive timestamps, so occasional gaps in the data do not skew it.
+    /// None when there are fewer than two distinct timestamps.
+    pub fn detect(timestamps: &[i64]) -> Option<Self> {
+        let mut intervals: Vec<i64> = timestamps
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .filter(|interval| *interval > 0)
+            .collect();
+        if intervals.is_empty() {
+            return None;
+        }
+        intervals.sort_unstable();
+        Some(Self { interval_ms: intervals[intervals.len() / 2] })
+    }
+
+    pub fn periods_per_year(&self) -> f64 {
+        DAYS_PER_YEAR * MS_PER_DAY as f64 / self.interval_ms as f64
+    }
+
+    pub const fn is_intraday(&self) -> bool {
+        self.interval_ms < MS_PER_DAY
+    }
+}
+
+impl fmt::Display for BarFrequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.interval_ms {
+            ms if ms % MS_PER_DAY == 0 => write!(f, "{}d", ms / MS_PER_DAY),
+            ms if ms % MS_PER_HOUR == 0 => write!(f, "{}h", ms / MS_PER_HOUR),
+            ms if ms % MS_PER_MINUTE == 0 => write!(f, "{}min", ms / MS_PER_MINUTE),
+            ms => write!(f, "{}ms", ms),
+        }
+    }
+}
+
+// Parses what Display writes, plus the "m" shorthand for minutes, e.g. "2min", "15m", "4h" or "1d"
+impl FromStr for BarFrequency {
+    type Err = PolarsError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || PolarsError::ComputeError(format!("Unknown timeframe '{}'", value).into());
+        let value = value.trim().to_lowercase();
+        let split = value.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+        let count: i64 = value[..split].parse().map_err(|_| invalid())?;
+        let unit = match &value[split..] {
+            "ms" => 1,
+            "s" => 1000,
+            "m" | "min" => MS_PER_MINUTE,
+            "h" => MS_PER_HOUR,
+            "d" => MS_PER_DAY,
+            _ => return Err(invalid()),
+        };
+        match count.checked_mul(unit) {
+            None | Some(0) => Err(invalid()),
+            Some(interval_ms) => Ok(Self { interval_ms }),
+        }
+    }
+}
+
+// Last value of every UTC day, stamped with the time of that day's last bar so intraday series align on it
+pub fn resample_to_daily(equity: &[(i64, f64)]) -> Vec<(i64, f64)> {
+    let mut daily: Vec<(i64, f64)> = vec![];
+    for &(timestamp, value) in equity {
+        match daily.last_mut() {
+            Some(last) if last.0.div_euclid(MS_PER_DAY) == timestamp.div_euclid(MS_PER_DAY) => *last = (timestamp, value),
+            _ => daily.push((timestamp, value)),
+        }
+    }
+    daily
+}
+
+/// Equity curve to annualize metrics on, together with its periods per year.
+/// Intraday curves are resampled to daily closes, coarser ones are kept at their own frequency.
+pub fn annualization_curve(equity: &[(i64, f64)]) -> (Vec<(i64, f64)>, f64) {
+    let timestamps: Vec<i64> = equity.iter().map(|point| point.0).collect();
+    match BarFrequency::detect(&timestamps) {
+        Some(frequency) if !frequency.is_intraday() => (equity.to_vec(), frequency.periods_per_year()),
+        _ => (resample_to_daily(equity), DAYS_PER_YEAR),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_frequency() {
+        let minutes: Vec<i64> = (0..10).map(|i| i * MS_PER_MINUTE).collect();
+        let frequency = BarFrequency::detect(&minutes).unwrap();
+        assert_eq!(frequency.interval_ms, MS_PER_MINUTE);
+        assert_eq!(frequency.periods_per_year(), 525_600.0);
+        assert_eq!(frequency.to_string(), "1min");
+
+        // A gap in the data does not change the detected spacing
+        let two_minutes = [0, 2, 4, 6, 30, 32].map(|i| i * MS_PER_MINUTE);
+        assert_eq!(BarFrequency::detect(&two_minutes).unwrap().to_string(), "2min");
+
+        let hours = [0, 1, 2].map(|i| i * MS_PER_HOUR);
+        assert_eq!(BarFrequency::detect(&hours).unwrap().periods_per_year(), 8760.0);
+
+        assert_eq!(BarFrequency::detect(&[0]), None);
+    }
+
+    #[test]
+    fn test_weekday() {
+        assert_eq!(weekday(0), 3); // Thursday
+        assert_eq!(weekday(4 * MS_PER_DAY), 0);
+        assert_eq!(weekday(4 * MS_PER_DAY - 1), 6);
+        assert_eq!(weekday(-MS_PER_DAY), 2);
+    }
+
+    #[test]
+    fn test_resample_to_daily() {
+        let equity = [
+            (0, 100.0),
+            (12 * MS_PER_HOUR, 105.0),
+            (MS_PER_DAY, 110.0),
+            (MS_PER_DAY + MS_PER_HOUR, 120.0),
+            (3 * MS_PER_DAY + 5, 90.0),
+        ];
+        assert_eq!(
+            resample_to_daily(&equity),
+            vec![(12 * MS_PER_HOUR, 105.0), (MS_PER_DAY + MS_PER_HOUR, 120.0), (3 * MS_PER_DAY + 5, 90.0)]
+        );
+
+        let (daily, periods_per_year) = annualization_curve(&equity);
+        assert_eq!(daily.len(), 3);
+        assert_eq!(periods_per_year, 365.0);
+
+        let weekly = [(0, 100.0), (7 * MS_PER_DAY, 110.0), (14 * MS_PER_DAY, 120.0)];
+        let (curve, periods_per_year) = annualization_curve(&weekly);
+        assert_eq!(curve, weekly.to_vec());
+        assert!((periods_per_year - 365.0 / 7.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_parse_frequency() {
+        assert_eq!("2min".parse::<BarFrequency>().unwrap().interval_ms, 2 * MS_PER_MINUTE);
+        assert_eq!("15m".parse::<BarFrequency>().unwrap().interval_ms, 15 * MS_PER_MINUTE);
+        assert_eq!("4H".parse::<BarFrequency>().unwrap().to_string(), "4h");
+        assert_eq!("1d".parse::<BarFrequency>().unwrap().interval_ms, MS_PER_DAY);
+        assert!("0d".parse::<BarFrequency>().is_err());
+        assert!("daily".parse::<BarFrequency>().is_err());
+        assert!("5w".parse::<BarFrequency>().is_err());
+        assert!("9999999999999999d".parse::<BarFrequency>().is_err());
+    }
+}
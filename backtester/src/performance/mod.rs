@@ -1 +1,6 @@
-pub mod performance;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod performance;
+pub mod report;
+pub mod risk;
+pub mod benchmark;
+pub mod frequency;
\ No newline at end of file
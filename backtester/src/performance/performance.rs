@@ -3,7 +3,7 @@ pub fn calculate_total_return(final_portfolio_value: f64, initial_capital: f64)
     (final_portfolio_value / initial_capital) - 1.0
 }
 
-fn calculate_annualized_base(total_return: f64, ticker: f64, num: u32) -> f64 {
+pub fn calculate_annualized_base(total_return: f64, ticker: f64, num: u32) -> f64 {
     // (1 + Return) ^ (ticker / N) - 1 = Annualized Return
     // https://www.investopedia.com/terms/a/annualized-total-return.asp
     (1.0 + total_return).powf(ticker / num as f64) - 1.0
@@ -34,29 +34,152 @@ pub fn calculate_annualized_daily_volatility(daily_returns: Series) -> f64 {
 }
 
 pub fn calculate_sharpe_ratio(annualized_return: f64, annualized_volatility: f64, risk_free_rate: f64) -> f64 {
+    // A flat curve has no volatility, report 0 rather than NaN or infinity
+    if annualized_volatility == 0.0 || annualized_volatility.is_nan() {
+        return 0.0;
+    }
     (annualized_return - risk_free_rate) / annualized_volatility
 }
 
 
-pub fn calculate_sortino_ratio(daily_returns: Series, annualized_return: f64, risk_free_rate: f64) -> f64 {
-    //let negative_returns = daily_returns.f64().expect("Not f64").get(0.0).into_series();
-    0.0
+pub fn calculate_annualized_downside_deviation(returns: Series, ticker: f64) -> f64 {
+    // Downside deviation only penalises negative returns, positive ones count as 0
+    let name = returns.name().clone();
+    let Ok(downside) = returns
+        .into_frame()
+        .lazy()
+        .select([when(col(name.clone()).lt(lit(0.0)))
+            .then(col(name.clone()) * col(name))
+            .otherwise(lit(0.0))
+            .mean()])
+        .collect()
+    else {
+        return 0.0;
+    };
+    let mean_square = downside.get_columns()[0].f64().ok().and_then(|column| column.get(0)).unwrap_or(0.0);
+    mean_square.sqrt() * ticker.sqrt()
+}
+
+pub fn calculate_sortino_ratio(returns: Series, ticker: f64, annualized_return: f64, risk_free_rate: f64) -> f64 {
+    let downside_deviation = calculate_annualized_downside_deviation(returns, ticker);
+    if downside_deviation == 0.0 {
+        return 0.0;
+    }
+    (annualized_return - risk_free_rate) / downside_deviation
+}
+
+// A run below the previous peak, indices are positions in the portfolio values
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawdownPeriod {
+    pub start: usize,             // Peak the drawdown is measured from
+    pub trough: usize,
+    pub recovery: Option<usize>,  // First value back at the peak, None if it never recovered
+    pub depth: f64,               // Negative fraction of the peak, -0.25 is a 25% drawdown
 }
 
-pub fn calculate_maximum_drawdown(_portfolio_values: Vec<f64>) -> f64 {
+impl DrawdownPeriod {
+    // Periods spent under water, up to the last of `len` values when it never recovered
+    pub fn duration(&self, len: usize) -> usize {
+        self.recovery.unwrap_or(len.saturating_sub(1)).saturating_sub(self.start)
+    }
+
+    pub fn time_to_recovery(&self) -> Option<usize> {
+        self.recovery.map(|recovery| recovery - self.trough)
+    }
+}
+
+pub fn calculate_underwater_curve(portfolio_values: &Series) -> Result<Series, PolarsError> {
     //drawdown = portfolio_values / portfolio_values.cummax() - 1
+    let values = portfolio_values.cast(&DataType::Float64)?;
+    let name = values.name().clone();
+    let underwater = values
+        .into_frame()
+        .lazy()
+        .select([(col(name.clone()) / col(name).cum_max(false) - lit(1.0)).alias("drawdown")])
+        .collect()?;
+    Ok(underwater.get_columns()[0].as_materialized_series().clone())
+}
+
+pub fn calculate_maximum_drawdown(portfolio_values: &Series) -> Result<f64, PolarsError> {
     //drawdown.min()
-    todo!("Implement calculate_maximum_drawdown");
-    //0.00
+    let underwater = calculate_underwater_curve(portfolio_values)?;
+    Ok(underwater.min::<f64>()?.unwrap_or(0.0).min(0.0))
+}
+
+pub fn calculate_drawdown_periods(portfolio_values: &Series) -> Result<Vec<DrawdownPeriod>, PolarsError> {
+    let underwater = calculate_underwater_curve(portfolio_values)?;
+    let mut periods: Vec<DrawdownPeriod> = vec![];
+    let mut current: Option<DrawdownPeriod> = None;
+
+    for (index, drawdown) in underwater.f64()?.into_iter().enumerate() {
+        let drawdown = drawdown.unwrap_or(0.0);
+        match current.as_mut() {
+            Some(period) if drawdown >= 0.0 => {
+                period.recovery = Some(index);
+                periods.extend(current.take());
+            }
+            Some(period) if drawdown < period.depth => {
+                period.depth = drawdown;
+                period.trough = index;
+            }
+            Some(_) => {}
+            None if drawdown < 0.0 => {
+                current = Some(DrawdownPeriod { start: index - 1, trough: index, recovery: None, depth: drawdown });
+            }
+            None => {}
+        }
+    }
+    periods.extend(current);
+    Ok(periods)
+}
+
+// Longest time under water in periods, including a drawdown that has not recovered yet
+pub fn calculate_maximum_drawdown_duration(portfolio_values: &Series) -> Result<usize, PolarsError> {
+    let periods = calculate_drawdown_periods(portfolio_values)?;
+    Ok(periods.iter().map(|period| period.duration(portfolio_values.len())).max().unwrap_or(0))
 }
 
-pub fn calculate_daily_returns(daily_values: &Vec<f64>) -> Result<DataFrame, PolarsError> {
+// Periods from the trough of the maximum drawdown back to its peak, None if it has not recovered
+pub fn calculate_time_to_recovery(portfolio_values: &Series) -> Result<Option<usize>, PolarsError> {
+    let periods = calculate_drawdown_periods(portfolio_values)?;
+    Ok(periods
+        .iter()
+        .min_by(|a, b| a.depth.total_cmp(&b.depth))
+        .and_then(DrawdownPeriod::time_to_recovery))
+}
+
+pub fn calculate_calmar_ratio(annualized_return: f64, maximum_drawdown: f64) -> f64 {
+    // Nothing was ever lost on a curve without drawdown, report 0 rather than NaN or infinity
+    if maximum_drawdown == 0.0 || maximum_drawdown.is_nan() {
+        return 0.0;
+    }
+    annualized_return / maximum_drawdown.abs()
+}
+
+pub fn calculate_win_rate(trade_pnls: &[f64]) -> f64 {
+    if trade_pnls.is_empty() {
+        return 0.0;
+    }
+    trade_pnls.iter().filter(|pnl| **pnl > 0.0).count() as f64 / trade_pnls.len() as f64
+}
+
+pub fn calculate_profit_factor(trade_pnls: &[f64]) -> f64 {
+    // Gross profit over gross loss, infinite when nothing was lost
+    if trade_pnls.is_empty() {
+        return 0.0;
+    }
+    let gross_profit: f64 = trade_pnls.iter().filter(|pnl| **pnl > 0.0).sum();
+    let gross_loss: f64 = trade_pnls.iter().filter(|pnl| **pnl < 0.0).sum::<f64>().abs();
+    gross_profit / gross_loss
+}
+
+pub fn calculate_daily_returns(daily_values: &[f64]) -> Result<DataFrame, PolarsError> {
     /* TODO Store Portfo    lio values and daily values as Dataframe in some extraction, to make it faster for final data crunching */
 
     // Convert the daily portfolio values to a Polars DataFrame/Series
     //let series = Series::new("portfolio_values".into(), daily_values.clone());
     let frame = df!(
-        "daily_values" => daily_values.clone()
+        "daily_values" => daily_values
     )?;
 
     //let pcv_change = series.lit().pct_change(col("portfolio_values")).drop_nans();
@@ -71,6 +194,7 @@ pub fn calculate_daily_returns(daily_values: &Vec<f64>) -> Result<DataFrame, Pol
     Ok(pct_change)
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
     #[test]
@@ -139,7 +263,56 @@ mod tests {
         let daily_returns = Series::new("daily_returns".into(), vec![0.01, 0.02, 0.03, 0.04, 0.05, 0.1, -0.3, -0.9, 0.4]);
         let annualized_return = 0.01;
         let risk_free_rate = 0.0;
-        let sortino_ratio = calculate_sortino_ratio(daily_returns, annualized_return, risk_free_rate);
-        assert_eq!(sortino_ratio, 0.0);
+        let sortino_ratio = calculate_sortino_ratio(daily_returns, 365.0, annualized_return, risk_free_rate);
+        // Downside deviation sqrt((0.3^2 + 0.9^2) / 9) * sqrt(365)
+        assert_eq!(sortino_ratio, 0.0016552117772047359);
+    }
+
+    #[test]
+    fn test_calculate_trade_statistics() {
+        let trade_pnls = [10.0, -5.0, 20.0, -5.0];
+        assert_eq!(calculate_win_rate(&trade_pnls), 0.5);
+        assert_eq!(calculate_profit_factor(&trade_pnls), 3.0);
+        assert_eq!(calculate_profit_factor(&[10.0]), f64::INFINITY);
+        assert_eq!(calculate_win_rate(&[]), 0.0);
+        assert_eq!(calculate_calmar_ratio(0.3, -0.15), 2.0);
+        assert_eq!(calculate_calmar_ratio(0.3, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_calculate_maximum_drawdown() {
+        let portfolio_values = Series::new("portfolio_values".into(), vec![100.0, 120.0, 90.0, 60.0, 150.0, 120.0]);
+        assert_eq!(calculate_maximum_drawdown(&portfolio_values).unwrap(), -0.5);
+
+        let rising = Series::new("portfolio_values".into(), vec![100.0, 110.0, 120.0]);
+        assert_eq!(calculate_maximum_drawdown(&rising).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_calculate_underwater_curve() {
+        let portfolio_values = Series::new("portfolio_values".into(), vec![100.0, 120.0, 90.0, 60.0, 160.0, 120.0]);
+        let underwater = calculate_underwater_curve(&portfolio_values).unwrap();
+        let drawdowns: Vec<f64> = underwater.f64().unwrap().into_no_null_iter().collect();
+        assert_eq!(drawdowns, vec![0.0, 0.0, -0.25, -0.5, 0.0, -0.25]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_calculate_drawdown_duration_and_recovery() {
+        let portfolio_values = Series::new("portfolio_values".into(), vec![100.0, 120.0, 90.0, 60.0, 160.0, 120.0]);
+        let periods = calculate_drawdown_periods(&portfolio_values).unwrap();
+        assert_eq!(periods, vec![
+            DrawdownPeriod { start: 1, trough: 3, recovery: Some(4), depth: -0.5 },
+            DrawdownPeriod { start: 4, trough: 5, recovery: None, depth: -0.25 },
+        ]);
+
+        assert_eq!(calculate_maximum_drawdown_duration(&portfolio_values).unwrap(), 3);
+        assert_eq!(calculate_time_to_recovery(&portfolio_values).unwrap(), Some(1));
+
+        // The deepest drawdown has not recovered by the last value
+        let unrecovered = Series::new("portfolio_values".into(), vec![100.0, 90.0, 100.0, 70.0, 80.0]);
+        assert_eq!(calculate_maximum_drawdown_duration(&unrecovered).unwrap(), 2);
+        let period = DrawdownPeriod { start: 0, trough: 0, recovery: None, depth: -0.1 };
+        assert_eq!(period.duration(0), 0);
+        assert_eq!(calculate_time_to_recovery(&unrecovered).unwrap(), None);
+    }
+}
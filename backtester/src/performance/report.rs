@@ -0,0 +1,207 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use polars::prelude::*;
+use crate::backtrader::ledger::Trade;
+use crate::performance::benchmark::BenchmarkMetrics;
+use crate::performance::frequency::BarFrequency;
+use crate::performance::risk::{RiskMetrics, DEFAULT_CONFIDENCE};
+use crate::performance::performance::{
+    calculate_annualized_base, calculate_annualized_volatility, calculate_calmar_ratio, calculate_daily_returns,
+    calculate_maximum_drawdown, calculate_maximum_drawdown_duration, calculate_profit_factor, calculate_sharpe_ratio, calculate_sortino_ratio,
+    calculate_time_to_recovery, calculate_total_return, calculate_win_rate,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerformanceMetrics {
+    pub initial_value: f64,
+    pub final_value: f64,
+    pub total_return: f64,
+    pub annualized_return: f64,
+    pub annualized_volatility: f64,
+    pub sharpe_ratio: f64,
+    pub sortino_ratio: f64,
+    pub maximum_drawdown: f64, // Negative fraction of the peak, -0.25 is a 25% drawdown
+    pub maximum_drawdown_duration: usize, // Longest run of periods below the previous peak
+    pub time_to_recovery: Option<usize>,  // Periods from the deepest trough back to its peak, None if not recovered
+    pub calmar_ratio: f64,
+    pub win_rate: f64,
+    pub profit_factor: f64,
+    pub exposure_time: f64, // Fraction of bars with an open position
+    pub trade_count: usize, // Closed trades only
+    pub risk: RiskMetrics,
+    pub benchmark: Option<BenchmarkMetrics>, // Only set when the backtest has a benchmark
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerformanceReport {
+    pub frequency: Option<BarFrequency>, // Detected bar spacing of the backtest, None with fewer than two bars
+    pub aggregate: PerformanceMetrics,
+    // Measured on each symbol's slice of the capital. With a shared cash pool a slice's cash goes negative
+    // when the symbol buys with the others' cash, so only the aggregate is meaningful for portfolio backtests
+    pub symbols: HashMap<String, PerformanceMetrics>,
+}
+
+impl PerformanceMetrics {
+    /// Metrics of a timestamped equity curve and the trades closed over it.
+    /// `periods_per_year` is the number of points of the curve in a year, used to annualize returns and volatility.
+    pub fn calculate(
+        equity: &[(i64, f64)],
+        initial_value: f64,
+        trades: &[&Trade],
+        exposure_time: f64,
+        periods_per_year: f64,
+        risk_free_rate: f64,
+    ) -> PolarsResult<Self> {
+        let values: Vec<f64> = equity.iter().map(|point| point.1).collect();
+        let final_value = values.last().copied().unwrap_or(initial_value);
+        let total_return = calculate_total_return(final_value, initial_value);
+
+        let returns = calculate_daily_returns(&values)?
+            .column("pct_change")?
+            .as_materialized_series()
+            .drop_nulls();
+        // The total return runs from the initial value before the first bar to the last one, one period per bar
+        let annualized_return = calculate_annualized_base(total_return, periods_per_year, equity.len().max(1) as u32);
+        let annualized_volatility = if returns.len() > 1 {
+            calculate_annualized_volatility(returns.clone(), periods_per_year)
+        } else {
+            0.0
+        };
+        let values = Series::new("equity".into(), values);
+        let maximum_drawdown = calculate_maximum_drawdown(&values)?;
+        let risk = RiskMetrics::calculate(&returns, &values, DEFAULT_CONFIDENCE)?;
+
+        let trade_pnls: Vec<f64> = trades.iter().filter_map(|trade| trade.pnl).collect();
+
+        Ok(Self {
+            initial_value,
+            final_value,
+            total_return,
+            annualized_return,
+            annualized_volatility,
+            sharpe_ratio: calculate_sharpe_ratio(annualized_return, annualized_volatility, risk_free_rate),
+            sortino_ratio: calculate_sortino_ratio(returns, periods_per_year, annualized_return, risk_free_rate),
+            maximum_drawdown,
+            maximum_drawdown_duration: calculate_maximum_drawdown_duration(&values)?,
+            time_to_recovery: calculate_time_to_recovery(&values)?,
+            calmar_ratio: calculate_calmar_ratio(annualized_return, maximum_drawdown),
+            win_rate: calculate_win_rate(&trade_pnls),
+            profit_factor: calculate_profit_factor(&trade_pnls),
+            exposure_time,
+            trade_count: trade_pnls.len(),
+            risk,
+            benchmark: None,
+        })
+    }
+}
+
+// Sum equity curves on the union of their timestamps, a symbol counts with its last value (or initial value) in between
+pub fn aggregate_equity(curves: &[(&[(i64, f64)], f64)]) -> Vec<(i64, f64)> {
+    let timestamps: BTreeSet<i64> = curves
+        .iter()
+        .flat_map(|(curve, _)| curve.iter().map(|point| point.0))
+        .collect();
+
+    let mut cursors = vec![0; curves.len()];
+    let mut last_values: Vec<f64> = curves.iter().map(|(_, initial)| *initial).collect();
+    timestamps
+        .into_iter()
+        .map(|timestamp| {
+            for (index, (curve, _)) in curves.iter().enumerate() {
+                while cursors[index] < curve.len() && curve[cursors[index]].0 <= timestamp {
+                    last_values[index] = curve[cursors[index]].1;
+                    cursors[index] += 1;
+                }
+            }
+            (timestamp, last_values.iter().sum())
+        })
+        .collect()
+}
+
+impl fmt::Display for PerformanceMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Final Portfolio Value: {:.2}", self.final_value)?;
+        writeln!(f, "Total Return: {:.2}%", self.total_return * 100.0)?;
+        writeln!(f, "Annualized Return: {:.2}%", self.annualized_return * 100.0)?;
+        writeln!(f, "Annualized Volatility: {:.2}%", self.annualized_volatility * 100.0)?;
+        writeln!(f, "Sharpe Ratio: {:.2}", self.sharpe_ratio)?;
+        writeln!(f, "Sortino Ratio: {:.2}", self.sortino_ratio)?;
+        writeln!(f, "Maximum Drawdown: {:.2}%", self.maximum_drawdown * 100.0)?;
+        writeln!(f, "Maximum Drawdown Duration: {} periods", self.maximum_drawdown_duration)?;
+        match self.time_to_recovery {
+            Some(periods) => writeln!(f, "Time to Recovery: {} periods", periods)?,
+            None => writeln!(f, "Time to Recovery: not recovered")?,
+        }
+        writeln!(f, "Calmar Ratio: {:.2}", self.calmar_ratio)?;
+        writeln!(f, "Win Rate: {:.2}%", self.win_rate * 100.0)?;
+        writeln!(f, "Profit Factor: {:.2}", self.profit_factor)?;
+        writeln!(f, "Exposure Time: {:.2}%", self.exposure_time * 100.0)?;
+        writeln!(f, "Trades: {}", self.trade_count)?;
+        write!(f, "{}", self.risk)?;
+        if let Some(benchmark) = &self.benchmark {
+            write!(f, "{}", benchmark)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PerformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(frequency) = self.frequency {
+            writeln!(f, "Bar Frequency: {}", frequency)?;
+        }
+        writeln!(f, "=== Portfolio ===")?;
+        write!(f, "{}", self.aggregate)?;
+
+        let mut symbols: Vec<&String> = self.symbols.keys().collect();
+        symbols.sort();
+        for symbol in symbols {
+            writeln!(f, "=== {} ===", symbol)?;
+            write!(f, "{}", self.symbols[symbol])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate_equity_carries_last_value() {
+        let first = [(1, 110.0), (3, 120.0)];
+        let second = [(2, 90.0), (3, 95.0)];
+        let aggregate = aggregate_equity(&[(&first, 100.0), (&second, 100.0)]);
+        assert_eq!(aggregate, vec![(1, 210.0), (2, 200.0), (3, 215.0)]);
+    }
+
+    #[test]
+    fn test_metrics_of_equity_curve() {
+        let equity = [(0, 100.0), (1, 110.0), (2, 99.0), (3, 121.0)];
+        let metrics = PerformanceMetrics::calculate(&equity, 100.0, &[], 0.5, 365.0, 0.0).unwrap();
+
+        assert!((metrics.total_return - 0.21).abs() < 1e-12);
+        assert!((metrics.maximum_drawdown - -0.1).abs() < 1e-12);
+        assert_eq!(metrics.maximum_drawdown_duration, 2);
+        assert_eq!(metrics.time_to_recovery, Some(1));
+        assert_eq!(metrics.trade_count, 0);
+        assert_eq!(metrics.exposure_time, 0.5);
+        assert!(metrics.sortino_ratio > metrics.sharpe_ratio);
+        // Four bars of the year's 365
+        assert!((metrics.annualized_return - (1.21f64.powf(365.0 / 4.0) - 1.0)).abs() < 1e-9 * metrics.annualized_return);
+    }
+
+    #[test]
+    fn test_metrics_of_flat_curve() {
+        let equity = [(0, 100.0), (1, 100.0), (2, 100.0), (3, 100.0)];
+        let metrics = PerformanceMetrics::calculate(&equity, 100.0, &[], 0.0, 365.0, 0.02).unwrap();
+
+        assert_eq!(metrics.annualized_volatility, 0.0);
+        assert_eq!(metrics.maximum_drawdown, 0.0);
+        assert_eq!(metrics.sharpe_ratio, 0.0);
+        assert_eq!(metrics.sortino_ratio, 0.0);
+        assert_eq!(metrics.calmar_ratio, 0.0);
+        assert_eq!(metrics.risk.skew, 0.0);
+        assert_eq!(metrics.risk.kurtosis, 0.0);
+    }
+}
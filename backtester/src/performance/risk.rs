@@ -0,0 +1,199 @@
+use std::fmt;
+use polars::prelude::*;
+use crate::performance::performance::calculate_underwater_curve;
+
+pub const DEFAULT_CONFIDENCE: f64 = 0.95;
+
+// Losses are reported as positive fractions, a VaR of 0.05 means 5% of the value can be lost in one period
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskMetrics {
+    pub confidence: f64,
+    pub historical_var: f64,
+    pub parametric_var: f64,
+    pub conditional_var: f64, // Expected shortfall, the mean loss beyond the historical VaR
+    pub omega_ratio: f64,
+    pub tail_ratio: f64,
+    pub ulcer_index: f64,
+    pub skew: f64,
+    pub kurtosis: f64, // Excess kurtosis, 0 for a normal distribution
+}
+
+impl RiskMetrics {
+    // Risk of the per period `returns` of an equity curve made of `portfolio_values`
+    pub fn calculate(returns: &Series, portfolio_values: &Series, confidence: f64) -> PolarsResult<Self> {
+        Ok(Self {
+            confidence,
+            historical_var: calculate_historical_var(returns, confidence)?,
+            parametric_var: calculate_parametric_var(returns, confidence)?,
+            conditional_var: calculate_conditional_var(returns, confidence)?,
+            omega_ratio: calculate_omega_ratio(returns, 0.0)?,
+            tail_ratio: calculate_tail_ratio(returns)?,
+            ulcer_index: calculate_ulcer_index(portfolio_values)?,
+            skew: calculate_skew(returns)?,
+            kurtosis: calculate_kurtosis(returns)?,
+        })
+    }
+}
+
+impl fmt::Display for RiskMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let confidence = self.confidence * 100.0;
+        writeln!(f, "Historical VaR ({:.0}%): {:.2}%", confidence, self.historical_var * 100.0)?;
+        writeln!(f, "Parametric VaR ({:.0}%): {:.2}%", confidence, self.parametric_var * 100.0)?;
+        writeln!(f, "Conditional VaR ({:.0}%): {:.2}%", confidence, self.conditional_var * 100.0)?;
+        writeln!(f, "Omega Ratio: {:.2}", self.omega_ratio)?;
+        writeln!(f, "Tail Ratio: {:.2}", self.tail_ratio)?;
+        writeln!(f, "Ulcer Index: {:.2}%", self.ulcer_index * 100.0)?;
+        writeln!(f, "Skew: {:.2}", self.skew)?;
+        writeln!(f, "Kurtosis: {:.2}", self.kurtosis)
+    }
+}
+
+fn sorted_values(returns: &Series) -> PolarsResult<Vec<f64>> {
+    let returns = returns.cast(&DataType::Float64)?;
+    let mut values: Vec<f64> = returns.f64()?.into_iter().flatten().filter(|value| !value.is_nan()).collect();
+    values.sort_by(f64::total_cmp);
+    Ok(values)
+}
+
+// Quantile with linear interpolation between the closest ranks
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = q * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+// Inverse of the standard normal CDF, Acklam's rational approximation (relative error below 1.15e-9)
+fn inverse_normal_cdf(p: f64) -> f64 {
+    const A: [f64; 6] = [-3.969683028665376e1, 2.209460984245205e2, -2.759285104469687e2, 1.38357751867269e2, -3.066479806614716e1, 2.506628277459239];
+    const B: [f64; 5] = [-5.447609879822406e1, 1.615858368580409e2, -1.556989798598866e2, 6.680131188771972e1, -1.328068155288572e1];
+    const C: [f64; 6] = [-7.784894002430293e-3, -3.223964580411365e-1, -2.400758277161838, -2.549732539343734, 4.374664141464968, 2.938163982698783];
+    const D: [f64; 4] = [7.784695709041462e-3, 3.224671290700398e-1, 2.445134137142996, 3.754408661907416];
+    const P_LOW: f64 = 0.02425;
+
+    if p < P_LOW {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5]) / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    } else if p <= 1.0 - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -inverse_normal_cdf(1.0 - p)
+    }
+}
+
+pub fn calculate_historical_var(returns: &Series, confidence: f64) -> PolarsResult<f64> {
+    // Loss that is only exceeded in (1 - confidence) of the observed periods
+    let sorted = sorted_values(returns)?;
+    Ok(-quantile(&sorted, 1.0 - confidence))
+}
+
+pub fn calculate_parametric_var(returns: &Series, confidence: f64) -> PolarsResult<f64> {
+    // Same loss assuming normally distributed returns: -(mean + z * std)
+    let returns = returns.cast(&DataType::Float64)?;
+    let mean = returns.mean().unwrap_or(0.0);
+    let std = returns.std(1).unwrap_or(0.0);
+    Ok(-(mean + inverse_normal_cdf(1.0 - confidence) * std))
+}
+
+pub fn calculate_conditional_var(returns: &Series, confidence: f64) -> PolarsResult<f64> {
+    let sorted = sorted_values(returns)?;
+    let threshold = quantile(&sorted, 1.0 - confidence);
+    let tail: Vec<f64> = sorted.into_iter().take_while(|value| *value <= threshold).collect();
+    if tail.is_empty() {
+        return Ok(0.0);
+    }
+    Ok(-tail.iter().sum::<f64>() / tail.len() as f64)
+}
+
+pub fn calculate_omega_ratio(returns: &Series, threshold: f64) -> PolarsResult<f64> {
+    // Gains above the threshold over the losses below it, infinite when nothing fell below it
+    let sorted = sorted_values(returns)?;
+    let gains: f64 = sorted.iter().map(|value| (value - threshold).max(0.0)).sum();
+    let losses: f64 = sorted.iter().map(|value| (threshold - value).max(0.0)).sum();
+    if gains == 0.0 {
+        return Ok(0.0);
+    }
+    Ok(gains / losses)
+}
+
+pub fn calculate_tail_ratio(returns: &Series) -> PolarsResult<f64> {
+    // Size of the right tail against the left one, above 1 when big gains outweigh big losses
+    let sorted = sorted_values(returns)?;
+    let left = quantile(&sorted, 0.05).abs();
+    if left == 0.0 {
+        return Ok(0.0);
+    }
+    Ok(quantile(&sorted, 0.95).abs() / left)
+}
+
+pub fn calculate_ulcer_index(portfolio_values: &Series) -> PolarsResult<f64> {
+    // Root mean square of the drawdowns, penalises both depth and duration
+    let underwater = calculate_underwater_curve(portfolio_values)?;
+    let drawdowns = underwater.f64()?;
+    if drawdowns.is_empty() {
+        return Ok(0.0);
+    }
+    let squared: f64 = drawdowns.into_iter().flatten().map(|drawdown| drawdown * drawdown).sum();
+    Ok((squared / drawdowns.len() as f64).sqrt())
+}
+
+// Both moments divide by the variance, a flat curve has none and gets 0 rather than NaN
+pub fn calculate_skew(returns: &Series) -> PolarsResult<f64> {
+    Ok(returns.cast(&DataType::Float64)?.skew(true)?.filter(|skew| !skew.is_nan()).unwrap_or(0.0))
+}
+
+pub fn calculate_kurtosis(returns: &Series) -> PolarsResult<f64> {
+    Ok(returns.cast(&DataType::Float64)?.kurtosis(true, true)?.filter(|kurtosis| !kurtosis.is_nan()).unwrap_or(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn returns() -> Series {
+        Series::new("returns".into(), vec![0.01, 0.02, 0.03, 0.04, 0.05, 0.1, -0.3, -0.9, 0.4])
+    }
+
+    #[test]
+    fn test_historical_var_and_expected_shortfall() {
+        // 5% quantile sits 40% of the way from -0.9 to -0.3
+        let var = calculate_historical_var(&returns(), 0.95).unwrap();
+        assert!((var - 0.66).abs() < 1e-12);
+
+        let cvar = calculate_conditional_var(&returns(), 0.95).unwrap();
+        assert_eq!(cvar, 0.9);
+    }
+
+    #[test]
+    fn test_parametric_var() {
+        assert!((inverse_normal_cdf(0.05) - -1.6448536269514729).abs() < 1e-8);
+        assert!((inverse_normal_cdf(0.5)).abs() < 1e-12);
+
+        let constant = Series::new("returns".into(), vec![0.01, 0.01, 0.01]);
+        assert!((calculate_parametric_var(&constant, 0.95).unwrap() - -0.01).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_omega_and_tail_ratio() {
+        // Gains 0.65 against losses 1.2
+        let omega = calculate_omega_ratio(&returns(), 0.0).unwrap();
+        assert!((omega - 0.65 / 1.2).abs() < 1e-12);
+
+        let symmetric = Series::new("returns".into(), vec![-0.1, -0.05, 0.0, 0.05, 0.1]);
+        assert!((calculate_tail_ratio(&symmetric).unwrap() - 1.0).abs() < 1e-12);
+        assert!(calculate_skew(&symmetric).unwrap().abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_ulcer_index() {
+        let portfolio_values = Series::new("portfolio_values".into(), vec![100.0, 50.0, 100.0, 100.0]);
+        assert_eq!(calculate_ulcer_index(&portfolio_values).unwrap(), 0.25);
+    }
+}
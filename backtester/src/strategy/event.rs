@@ -0,0 +1,58 @@
+use crate::backtrader::asset_data::AssetData;
+use crate::backtrader::ledger::Rejection;
+use crate::backtrader::order::Order;
+use crate::backtrader::order_book::{Fill, OrderBook, OrderId, PendingOrder};
+use crate::data::candle::Candle;
+
+/// What an event driven strategy sees of the portfolio when it is called, and how it trades.
+/// Orders it submits are matched from the next bar on, like the orders of a vectorized strategy.
+pub struct StrategyContext<'a> {
+    pub symbol: &'a str,
+    pub timestamp: i64,
+    pub price: f64,  // Last known price of the symbol, the close of the bar once it is complete
+    pub equity: f64, // Equity positions are sized on, the whole portfolio when cash is shared
+    pub asset: &'a AssetData,
+    pub(crate) order_book: &'a mut OrderBook,
+}
+
+impl StrategyContext<'_> {
+    pub const fn cash(&self) -> f64 {
+        self.asset.cash
+    }
+
+    pub const fn position(&self) -> f64 {
+        self.asset.positions
+    }
+
+    // Place an order, limit and stop prices are moved onto the symbol's tick grid
+    pub fn submit(&mut self, mut order: Order) -> OrderId {
+        self.asset.rules.round_prices(&mut order);
+        self.order_book.submit(order, self.timestamp)
+    }
+
+    pub fn cancel(&mut self, id: OrderId) -> Option<Order> {
+        self.order_book.cancel(id)
+    }
+
+    pub fn cancel_all(&mut self) -> Vec<Order> {
+        self.order_book.cancel_all(self.symbol)
+    }
+
+    pub fn pending(&self) -> &[PendingOrder] {
+        self.order_book.pending(self.symbol)
+    }
+}
+
+/// A strategy that is called bar by bar and keeps its own state, e.g. a grid, a martingale or a model.
+/// Fills and rejections of a bar are reported before `on_bar` is called for it.
+pub trait EventStrategy {
+    fn on_start(&mut self, _context: &mut StrategyContext) {}
+
+    fn on_bar(&mut self, candle: &Candle, context: &mut StrategyContext);
+
+    fn on_fill(&mut self, _fill: &Fill, _context: &mut StrategyContext) {}
+
+    fn on_order_rejected(&mut self, _rejection: &Rejection, _context: &mut StrategyContext) {}
+
+    fn on_finish(&mut self, _context: &mut StrategyContext) {}
+}
@@ -0,0 +1,51 @@
+use polars::prelude::*;
+
+fn max_expr(left: Expr, right: Expr) -> Expr {
+    when(left.clone().gt_eq(right.clone())).then(left).otherwise(right)
+}
+
+// True range: the largest of the bar's range and the gaps from the previous close
+// As high >= low, |high - prev| and |low - prev| reduce to high - prev and prev - low
+pub fn true_range() -> Expr {
+    // The first bar has no previous close, its true range is its own range
+    let previous_close = col("close").shift(lit(1)).fill_null(col("close"));
+    max_expr(
+        col("high") - col("low"),
+        max_expr(col("high") - previous_close.clone(), previous_close - col("low")),
+    )
+}
+
+// Simple moving average of the true range over `window` bars, null until the window is filled
+pub fn average_true_range(window: usize) -> Expr {
+    true_range().rolling_mean(RollingOptionsFixedWindow {
+        window_size: window,
+        min_periods: window,
+        ..RollingOptionsFixedWindow::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_true_range() -> PolarsResult<()> {
+        let frame = df!(
+            "high" => [11.0, 12.0, 15.0],
+            "low" => [9.0, 10.0, 13.0],
+            "close" => [10.0, 11.0, 14.0]
+        )?;
+
+        let result = frame
+            .lazy()
+            .select([true_range().alias("tr"), average_true_range(2).alias("atr")])
+            .collect()?;
+
+        // The last bar gaps up, its true range reaches back to the previous close
+        let tr: Vec<Option<f64>> = result.column("tr")?.f64()?.into_iter().collect();
+        assert_eq!(tr, vec![Some(2.0), Some(2.0), Some(4.0)]);
+        let atr: Vec<Option<f64>> = result.column("atr")?.f64()?.into_iter().collect();
+        assert_eq!(atr, vec![None, Some(2.0), Some(3.0)]);
+        Ok(())
+    }
+}
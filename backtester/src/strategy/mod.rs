@@ -1 +1,4 @@
-pub mod strategy;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod strategy;
+pub mod indicators;
+pub mod event;
\ No newline at end of file
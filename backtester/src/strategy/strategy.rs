@@ -1,13 +1,100 @@
 use polars::prelude::*;
 use std::sync::Arc;
+use crate::backtrader::order::ORDER_COLUMNS;
 
 // Define a type for an indicator function.
 // It takes and modifies a `DataFrame` (e.g., adding a new column).
 pub type IndicatorFn = Arc<dyn Fn(&mut DataFrame) -> PolarsResult<()>>;
 
+// Columns the backtester needs from every signal frame, the candle is used to fill resting orders on later bars
+pub const SIGNAL_COLUMNS: [&str; 7] = ["timestamp", "open", "high", "low", "close", "volume", "signal"];
+
+// Target weights of a rebalancing strategy are read from `weight_<symbol>` columns
+pub const WEIGHT_PREFIX: &str = "weight_";
+
+// Fraction of equity to hold, -1 fully short to 1 fully long, taken from a float `signal` column
+pub const EXPOSURE_COLUMN: &str = "exposure";
+
+/// How the engine reads the `signal` column, either way it only trades on the bars where the signal changes.
+/// Null signals leave the previous value in place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignalKind {
+    // 1 enters and -1 exits, repeating the same event on the following bars does nothing.
+    // An exit only sells the long position, it never goes on past flat into a short.
+    #[default]
+    Event,
+    // Target position, 1 long, 0 flat and -1 short, e.g. whether a fast average is above a slow one
+    State,
+}
+
+/// Validate the `signal` column and bring it into the form the engine reads, an Int8 of -1, 0 and 1.
+/// Booleans are 1 or 0, integers must already be -1, 0 or 1 and floats are a target exposure in [-1, 1],
+/// which is kept in the `exposure` column next to its sign.
+/// Signal logic is an expression whose type and values are only known on data, so this runs when the
+/// signals are generated, before a backtest trades its first bar, rather than when the strategy is built.
+pub fn normalize_signals(df: DataFrame) -> PolarsResult<DataFrame> {
+    let signal = df.column("signal")?.as_materialized_series();
+    let dtype = signal.dtype().clone();
+    let out_of_range = |low: f64, high: f64| -> PolarsResult<bool> {
+        let min = signal.min::<f64>()?.unwrap_or(0.0);
+        let max = signal.max::<f64>()?.unwrap_or(0.0);
+        Ok(!(min >= low && max <= high) || (dtype.is_float() && signal.is_nan()?.any()))
+    };
+
+    match dtype {
+        DataType::Boolean | DataType::Null => df.lazy().with_column(col("signal").cast(DataType::Int8)).collect(),
+        _ if dtype.is_integer() => {
+            if out_of_range(-1.0, 1.0)? {
+                return Err(PolarsError::ComputeError("Integer signals must be -1, 0 or 1".into()));
+            }
+            df.lazy().with_column(col("signal").cast(DataType::Int8)).collect()
+        }
+        _ if dtype.is_float() => {
+            if out_of_range(-1.0, 1.0)? {
+                return Err(PolarsError::ComputeError("Target exposure signals must be within [-1, 1]".into()));
+            }
+            let direction = when(col("signal").is_null())
+                .then(lit(NULL))
+                .when(col("signal").gt(lit(0.0)))
+                .then(lit(1))
+                .when(col("signal").lt(lit(0.0)))
+                .then(lit(-1))
+                .otherwise(lit(0));
+            df.lazy()
+                .with_columns([
+                    col("signal").cast(DataType::Float64).alias(EXPOSURE_COLUMN),
+                    direction.cast(DataType::Int8).alias("signal"),
+                ])
+                .collect()
+        }
+        other => Err(PolarsError::ComputeError(
+            format!("Signal column must be boolean, integer or float, got {}", other).into(),
+        )),
+    }
+}
+
 pub trait StrategyTrait {
     fn generate_signals(&self, data: &mut &Option<DataFrame>) -> PolarsResult<DataFrame>;
     fn apply_strategy(&self, df: &mut &Option<DataFrame>) -> PolarsResult<DataFrame>;
+
+    fn signal_kind(&self) -> SignalKind {
+        SignalKind::Event
+    }
+
+    // Timestamp and the `weight_<symbol>` columns the strategy produced
+    fn generate_weights(&self, data: &mut &Option<DataFrame>) -> PolarsResult<DataFrame> {
+        select_weights(self.apply_strategy(data)?)
+    }
+}
+
+fn select_weights(df: DataFrame) -> PolarsResult<DataFrame> {
+    let columns: Vec<PlSmallStr> = df
+        .get_column_names()
+        .into_iter()
+        .filter(|name| name.as_str() == "timestamp" || name.starts_with(WEIGHT_PREFIX))
+        .cloned()
+        .collect();
+    df.select(columns)
 }
 
 
@@ -15,18 +102,24 @@ pub trait StrategyTrait {
 #[derive(Debug)]
 pub struct Strategy<E: AsRef<[Expr]>, T: AsRef<[Expr]>> {
     indicators: E,
-    signal_logic: T
+    signal_logic: T,
+    signal_kind: SignalKind,
 }
 impl<E: AsRef<[Expr]>, T: AsRef<[Expr]>> Strategy<E, T> {
-    pub fn new(
+    pub const fn new(
         indicators: E,
         signal_logic: T
     ) -> Self {
         Self {
             indicators,
             signal_logic,
+            signal_kind: SignalKind::Event,
         }
     }
+
+    pub const fn set_signal_kind(&mut self, signal_kind: SignalKind) {
+        self.signal_kind = signal_kind;
+    }
 }
 
 impl<E: AsRef<[Expr]>, T: AsRef<[Expr]>> StrategyTrait for Strategy<E, T> {
@@ -38,14 +131,54 @@ impl<E: AsRef<[Expr]>, T: AsRef<[Expr]>> StrategyTrait for Strategy<E, T> {
             .clone()
             .lazy()
             .with_columns(self.signal_logic.as_ref())
-            .select([col("timestamp"), col("signal"), col("close")])
             .collect()?;
-        Ok(signals)
+
+        // Order columns are optional, only keep the ones the signal logic produced
+        let schema = signals.schema();
+        let columns = SIGNAL_COLUMNS
+            .iter()
+            .chain(ORDER_COLUMNS.iter().filter(|name| schema.contains(name)));
+        normalize_signals(signals.select(columns.copied())?)
+    }
+
+    /// Weights may come from the indicators or the signal logic.
+    fn generate_weights(&self, data: &mut &Option<DataFrame>) -> PolarsResult<DataFrame> {
+        let indicators = self.apply_strategy(data)?;
+        select_weights(indicators.lazy().with_columns(self.signal_logic.as_ref()).collect()?)
+    }
+
+    fn signal_kind(&self) -> SignalKind {
+        self.signal_kind
     }
 
     /// Apply the entire strategy (indicators and signal logic) to the DataFrame.
     fn apply_strategy(&self, df: &mut &Option<DataFrame>) -> PolarsResult<DataFrame> {
         // Apply all indicators to the DataFrame, adding new columns
-        Ok(df.clone().unwrap().lazy().with_columns(self.indicators.as_ref()).collect()?)
+        df.clone().unwrap().lazy().with_columns(self.indicators.as_ref()).collect()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_signals() {
+        let booleans = df!("signal" => [Some(true), Some(false), None]).unwrap();
+        let signals = normalize_signals(booleans).unwrap();
+        assert_eq!(signals.column("signal").unwrap().i8().unwrap().to_vec(), vec![Some(1), Some(0), None]);
+
+        let ternary = df!("signal" => [-1i32, 0, 1]).unwrap();
+        assert_eq!(normalize_signals(ternary).unwrap().column("signal").unwrap().dtype(), &DataType::Int8);
+        assert!(normalize_signals(df!("signal" => [2i32, 0]).unwrap()).is_err());
+
+        let exposure = df!("signal" => [Some(0.5), Some(-0.25), Some(0.0), None]).unwrap();
+        let signals = normalize_signals(exposure).unwrap();
+        assert_eq!(signals.column("signal").unwrap().i8().unwrap().to_vec(), vec![Some(1), Some(-1), Some(0), None]);
+        assert_eq!(signals.column(EXPOSURE_COLUMN).unwrap().f64().unwrap().get(1), Some(-0.25));
+        assert!(normalize_signals(df!("signal" => [1.5]).unwrap()).is_err());
+        assert!(normalize_signals(df!("signal" => [f64::NAN]).unwrap()).is_err());
+
+        assert!(normalize_signals(df!("signal" => ["buy"]).unwrap()).is_err());
+    }
+}
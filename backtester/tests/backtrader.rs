@@ -1,20 +1,55 @@
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+    use std::fs::File;
+    use polars::df;
     use polars::error::PolarsResult;
-    use polars::prelude::{col, RollingOptionsFixedWindow};
-    use Backtester::backtrader::backtrader::Backtrader;
-    use Backtester::strategy::strategy::Strategy;
-    use super::*;
+    use polars::prelude::{col, lit, when, DataFrame, DataType, Expr, IntoLazy, ParquetWriter, RollingOptionsFixedWindow, TimeUnit};
+    use Backtester::backtrader::backtrader::{Backtrader, EXIT_ORDER_ID, LIQUIDATION_ORDER_ID};
+    use Backtester::backtrader::exchange_profile::{ExchangeProfile, SymbolRules};
+    use Backtester::backtrader::exits::{ExitLevel, ExitReason, ExitRules};
+    use Backtester::backtrader::fees::{FeeSchedule, FeeTier};
+    use Backtester::backtrader::funding::FundingRates;
+    use Backtester::backtrader::margin::MarginConfig;
+    use Backtester::backtrader::order::{Order, OrderType, Side, TimeInForce};
+    use Backtester::backtrader::position_sizer::FixedFraction;
+    use Backtester::backtrader::rebalance::RebalanceSchedule;
+    use Backtester::backtrader::slippage::FixedBps;
+    use Backtester::performance::benchmark::Benchmark;
+    use Backtester::backtrader::ledger::Rejection;
+    use Backtester::backtrader::order_book::Fill;
+    use Backtester::data::candle::Candle;
+    use Backtester::data::source::{DataSource, DateRange};
+    use Backtester::data::stream::CandleStream;
+    use Backtester::strategy::event::{EventStrategy, StrategyContext};
+    use Backtester::strategy::strategy::{SignalKind, Strategy};
 
-    /* TODO: Test case with two symbols */
+    // One minute bars of (open, high, low, close), volume is fixed at 10
+    fn candles(bars: &[(f64, f64, f64, f64)]) -> PolarsResult<DataFrame> {
+        candles_every(bars, 60_000)
+    }
 
+    fn candles_every(bars: &[(f64, f64, f64, f64)], interval_ms: i64) -> PolarsResult<DataFrame> {
+        let timestamps: Vec<i64> = (0..bars.len() as i64).map(|i| i * interval_ms).collect();
+        df!(
+            "timestamp" => timestamps,
+            "open" => bars.iter().map(|bar| bar.0).collect::<Vec<f64>>(),
+            "high" => bars.iter().map(|bar| bar.1).collect::<Vec<f64>>(),
+            "low" => bars.iter().map(|bar| bar.2).collect::<Vec<f64>>(),
+            "close" => bars.iter().map(|bar| bar.3).collect::<Vec<f64>>(),
+            "volume" => vec![10.0; bars.len()],
+        )?
+            .lazy()
+            .with_column(col("timestamp").cast(DataType::Datetime(TimeUnit::Milliseconds, None)))
+            .collect()
+    }
 
 
     #[test]
     fn test_strategy_with_backtrader() -> PolarsResult<()> {
         println!("Booting strategy!");
 
-        let symbol = "BTCUSDT";
+        let _symbol = "BTCUSDT";
         let start_date = "2023-01-01";
         let end_date = "2023-12-31";
         // TODO implement ticker! just a simple todo as if thats simple at all....
@@ -38,10 +73,12 @@ mod tests {
             col("sma_20").gt(col("sma_60")).alias("signal")
         ];
 
-        let strategy = Strategy::new(
+        // The crossover holds while the fast average is above the slow one, so it is a target position
+        let mut strategy = Strategy::new(
             indicator_expr,
             signal_expr
         );
+        strategy.set_signal_kind(SignalKind::State);
 
         let mut backtrader = Backtrader::new(
             1000.0,
@@ -49,10 +86,13 @@ mod tests {
             1.0,
             vec![&"BTCUSDT".to_string()],
         );
+        backtrader.set_data_sources(DataSource::from_file("examples/config/data_sources.json")?);
 
-        backtrader.backtest(Some("BTCUSDT".to_string()), strategy).unwrap();
+        let range = DateRange::parse(Some(start_date), Some(end_date))?;
+        backtrader.backtest(Some("BTCUSDT".to_string()), strategy, range).unwrap();
 
-        backtrader.calculate_performance(false)?;
+        let report = backtrader.calculate_performance(false)?;
+        println!("{}", report);
 
         println!("{:?}", backtrader);
         Ok(
@@ -62,6 +102,966 @@ mod tests {
 
 
     #[test]
+    fn test_limit_order_fills_on_later_bar() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 96.0, 100.0), // Does not reach the limit
+            (97.0, 98.0, 94.0, 96.0),    // Trades through the limit
+        ])?;
+
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [
+                col("close").gt(lit(0.0)).alias("signal"),
+                lit("limit").alias("order_type"),
+                lit(95.0).alias("limit_price"),
+            ],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.001, 1.0, vec![&symbol]);
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        let asset = backtrader.get_asset(&symbol).unwrap();
+        assert_eq!(asset.cash, 0.0);
+        // The fixed commission of 1.0 outweighs 0.1% of the notional
+        assert!((asset.positions - 999.0 / 95.0).abs() < 1e-9);
+
+        // Only the first bar's signal placed an order, it rested until the limit was reached
+        assert_eq!(backtrader.fills().len(), 1);
+        assert_eq!(backtrader.fills()[0].timestamp, 120_000);
+        assert_eq!(backtrader.fills()[0].order_id, 1);
+        assert!(backtrader.order_book().pending(&symbol).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resting_limit_pays_maker_fee() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (97.0, 98.0, 94.0, 96.0), // Trades down to the limit
+            (96.0, 97.0, 95.0, 96.0),
+        ])?;
+
+        // Only the first bar closes above 99, its limit order rests until the second bar reaches it
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [
+                col("close").gt(lit(99.0)).alias("signal"),
+                lit("limit").alias("order_type"),
+                lit(95.0).alias("limit_price"),
+            ],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_fee_schedule(FeeSchedule {
+            tiers: vec![FeeTier { min_volume: 0.0, maker: 0.001, taker: 0.002 }],
+            ..FeeSchedule::flat(0.0, 0.0)
+        });
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        let fill = &backtrader.fills()[0];
+        assert_eq!(fill.price, 95.0);
+        // Sized and charged at the maker rate, so the whole cash is spent
+        let trade_value = fill.price * fill.quantity;
+        assert!((trade_value - 1000.0 / 1.001).abs() < 1e-9);
+        assert!((fill.commission - trade_value * 0.001).abs() < 1e-9);
+        assert!(backtrader.get_asset(&symbol).unwrap().cash.abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_market_order_fills_on_next_open() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (110.0, 111.0, 109.0, 110.0),
+            (120.0, 121.0, 119.0, 120.0),
+        ])?;
+
+        // Only the first bar signals
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [col("close").lt(lit(105.0)).alias("signal")],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        let fills = backtrader.fills();
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].timestamp, 60_000);
+        assert_eq!(fills[0].price, 110.0);
+        assert!((backtrader.get_asset(&symbol).unwrap().positions - 1000.0 / 110.0).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_or_kill_needs_the_cash_for_all_of_it() -> PolarsResult<()> {
+        // The bars trade enough volume for any of the orders, only the cash limits them
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+        ])?
+            .lazy()
+            .with_column(lit(1000.0).alias("volume"))
+            .collect()?;
+        let symbol = "BTCUSDT".to_string();
+        // 1000 in cash buys 10 at 100, both orders ask for 15
+        let run = |time_in_force: TimeInForce| -> PolarsResult<Backtrader> {
+            let strategy = Strategy::new([] as [Expr; 0], [lit(false).alias("signal")]);
+            let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+            backtrader.set_data(&symbol, data.clone());
+            let order = Order { quantity: Some(15.0), time_in_force, ..Order::market(&symbol, Side::Buy) };
+            backtrader.order_book_mut().submit(order, 0);
+            backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+            Ok(backtrader)
+        };
+
+        // Immediate or cancel takes what the cash allows
+        let backtrader = run(TimeInForce::IOC)?;
+        assert_eq!(backtrader.fills().len(), 1);
+        assert_eq!(backtrader.fills()[0].quantity, 10.0);
+
+        // Fill or kill is killed instead of partially filled
+        let backtrader = run(TimeInForce::FOK)?;
+        assert!(backtrader.fills().is_empty());
+        assert!(backtrader.order_book().pending(&symbol).is_empty());
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().cash, 1000.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_slippage_moves_fill_price() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (110.0, 111.0, 109.0, 110.0),
+            (120.0, 121.0, 119.0, 120.0),
+        ])?;
+
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [col("close").lt(lit(105.0)).alias("signal")],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_slippage_model(FixedBps { bps: 10.0 });
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        // Bought at the next open plus 10 bps, all in at the worse price
+        let fill = &backtrader.fills()[0];
+        assert!((fill.price - 110.11).abs() < 1e-9);
+        assert!((fill.quantity - 1000.0 / 110.11).abs() < 1e-9);
+        assert!((fill.slippage - 0.11 * fill.quantity).abs() < 1e-9);
+        assert!(backtrader.get_asset(&symbol).unwrap().cash.abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_limit_orders_fill_within_their_limit_with_slippage() -> PolarsResult<()> {
+        let symbol = "BTCUSDT".to_string();
+        let limit_fill = |second_bar: (f64, f64, f64, f64)| -> PolarsResult<Fill> {
+            let strategy = Strategy::new([] as [Expr; 0], [lit(false).alias("signal")]);
+            let order = Order {
+                order_type: OrderType::Limit { limit_price: 95.0 },
+                quantity: Some(1.0),
+                ..Order::market(&symbol, Side::Buy)
+            };
+            let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+            backtrader.set_slippage_model(FixedBps { bps: 10.0 });
+            backtrader.set_data(&symbol, candles(&[(100.0, 101.0, 99.0, 100.0), second_bar])?);
+            backtrader.order_book_mut().submit(order, 0);
+            backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+            Ok(backtrader.fills()[0].clone())
+        };
+
+        // Gapping just below the limit takes liquidity at the open, slipping to 95.08 is capped at the limit
+        let taker = limit_fill((94.99, 96.0, 94.0, 95.0))?;
+        assert_eq!(taker.price, 95.0);
+        assert!((taker.slippage - 0.01).abs() < 1e-9);
+
+        // Trading down to the resting limit fills it as a maker, without slippage
+        let maker = limit_fill((96.0, 97.0, 94.0, 95.0))?;
+        assert_eq!(maker.price, 95.0);
+        assert_eq!(maker.slippage, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_exchange_profile_enforces_order_constraints() -> PolarsResult<()> {
+        let data = candles(&[
+            (30000.0, 30100.0, 29900.0, 30000.0),
+            (30000.0, 30100.0, 29900.0, 30000.0),
+        ])?;
+        let strategy = || Strategy::new(
+            [] as [Expr; 0],
+            [col("timestamp").cast(DataType::Int64).eq(lit(0)).alias("signal")],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let unlisted = "DOGEEUR".to_string();
+        assert!(Backtrader::with_exchange(1000.0, &ExchangeProfile::binance(), vec![&unlisted]).is_err());
+
+        // 1000 / (30000 * 1.001) is cut down to Binance's lot size of 0.00001 BTC
+        let mut backtrader = Backtrader::with_exchange(1000.0, &ExchangeProfile::binance(), vec![&symbol])?;
+        assert_eq!(backtrader.exchange_name(), "Binance");
+        backtrader.set_data(&symbol, data.clone());
+        backtrader.backtest(Some(symbol.clone()), strategy(), DateRange::default())?;
+        let fill = &backtrader.fills()[0];
+        assert!((fill.quantity - 0.0333).abs() < 1e-12);
+        assert!(backtrader.rejections().is_empty());
+
+        // Four dollars are below the minimum notional of five, the order is rejected
+        let mut backtrader = Backtrader::with_exchange(4.0, &ExchangeProfile::binance(), vec![&symbol])?;
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy(), DateRange::default())?;
+        assert!(backtrader.fills().is_empty());
+        let rejection = &backtrader.ledger().rejections()[0];
+        assert_eq!(rejection.symbol, symbol);
+        assert!(rejection.reason.contains("notional"));
+        assert_eq!(backtrader.ledger().rejections_to_dataframe()?.height(), 1);
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().cash, 4.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_instrument_rules_round_fills() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (110.0, 111.0, 109.0, 110.0),
+            (120.0, 121.0, 119.0, 120.0),
+        ])?;
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [col("close").lt(lit(105.0)).alias("signal")],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_rules(&symbol, SymbolRules { tick_size: 0.5, lot_size: 0.1, min_quantity: 0.1, min_notional: 10.0 });
+        backtrader.set_slippage_model(FixedBps { bps: 10.0 });
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        // 110.11 after slippage is moved up to the next tick, 1000 / 110.5 down to the lot size
+        let fill = &backtrader.fills()[0];
+        assert_eq!(fill.price, 110.5);
+        assert!((fill.quantity - 9.0).abs() < 1e-9);
+        assert!((backtrader.get_asset(&symbol).unwrap().cash - 5.5).abs() < 1e-9);
+        assert!(backtrader.ledger().rejections().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_buy_and_hold_benchmark() -> PolarsResult<()> {
+        let data = candles_every(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 111.0, 99.0, 110.0),
+            (110.0, 122.0, 109.0, 121.0),
+        ], 86_400_000)?;
+
+        // Buys on the first bar and fills at the second bar's open, so it tracks holding the asset
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [col("timestamp").cast(DataType::Int64).eq(lit(0)).alias("signal")],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_data(&symbol, data);
+        backtrader.set_benchmark(Benchmark::BuyAndHold);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        let report = backtrader.calculate_performance(false)?;
+        assert_eq!(report.frequency.map(|frequency| frequency.periods_per_year()), Some(365.0));
+        let benchmark = report.aggregate.benchmark.expect("Benchmark metrics missing");
+        assert!((benchmark.beta - 1.0).abs() < 1e-9);
+        assert!(benchmark.tracking_error.abs() < 1e-9);
+        assert!((benchmark.up_capture - 1.0).abs() < 1e-9);
+        assert!(report.symbols[&symbol].benchmark.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_portfolio_shares_cash_across_symbols() -> PolarsResult<()> {
+        let first = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 111.0, 99.0, 110.0),
+            (110.0, 122.0, 109.0, 120.0),
+        ])?;
+        // The second symbol has no bar at the third timestamp
+        let second = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (90.0, 91.0, 89.0, 90.0),
+        ])?
+            .lazy()
+            .with_column(col("timestamp").cast(DataType::Int64))
+            .with_column(
+                polars::prelude::when(col("timestamp").eq(lit(120_000)))
+                    .then(lit(180_000))
+                    .otherwise(col("timestamp"))
+                    .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
+                    .alias("timestamp"),
+            )
+            .collect()?;
+
+        // Only the first symbol signals, on the first bar while it trades below the second one's last close
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [col("symbol")
+                .eq(lit("AAA"))
+                .and(col("timestamp").cast(DataType::Int64).eq(lit(0)))
+                .and(col("close").lt_eq(col("close_BBB")))
+                .alias("signal")],
+        );
+
+        let symbols = ["AAA".to_string(), "BBB".to_string()];
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, symbols.iter().collect());
+        backtrader.set_data(&symbols[0], first);
+        backtrader.set_data(&symbols[1], second);
+
+        let aligned = backtrader.aligned_data(DateRange::default())?;
+        assert_eq!(aligned[0].1.height(), 4);
+        // The missing bar is null, the other symbol sees its last close
+        assert_eq!(aligned[1].1.column("close")?.null_count(), 1);
+        assert_eq!(aligned[0].1.column("close_BBB")?.f64()?.get(2), Some(100.0));
+
+        backtrader.backtest_portfolio(strategy, DateRange::default())?;
+
+        // The whole pool went into the first symbol
+        let first = backtrader.get_asset(&symbols[0]).unwrap();
+        assert_eq!(first.positions, 10.0);
+        assert_eq!(first.cash, -500.0);
+        assert_eq!(backtrader.get_asset(&symbols[1]).unwrap().positions, 0.0);
+
+        // Both symbols are marked on every timestamp of the shared clock
+        assert_eq!(backtrader.equity_curve(&symbols[1])?.height(), 4);
+        let report = backtrader.calculate_performance(false)?;
+        assert_eq!(report.aggregate.final_value, 1200.0);
+
+        // A later backtest sizes from the symbol's own cash again instead of the exhausted pool
+        let strategy = Strategy::new([] as [Expr; 0], [lit(true).alias("signal")]);
+        backtrader.backtest(Some(symbols[1].clone()), strategy, DateRange::default())?;
+        assert_eq!(backtrader.get_asset(&symbols[1]).unwrap().positions, 5.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebalance_to_target_weights() -> PolarsResult<()> {
+        let day = 86_400_000;
+        let first = candles_every(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 201.0, 99.0, 200.0),
+            (200.0, 201.0, 199.0, 200.0),
+        ], day)?;
+        let second = candles_every(&[(100.0, 101.0, 99.0, 100.0); 4], day)?;
+
+        // Equal weights from the first bar on
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [lit(0.5).alias("weight_AAA"), lit(0.5).alias("weight_BBB")],
+        );
+
+        let symbols = ["AAA".to_string(), "BBB".to_string()];
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, symbols.iter().collect());
+        backtrader.set_data(&symbols[0], first);
+        backtrader.set_data(&symbols[1], second);
+        backtrader.backtest_weights(strategy, RebalanceSchedule::Daily, DateRange::default())?;
+
+        // Bought 5 of each, then sold 1.25 of the first after it doubled to buy 2.5 of the second
+        let fills = backtrader.fills();
+        assert_eq!(fills.len(), 4);
+        assert_eq!(fills[2].side, Side::Sell);
+        assert_eq!(fills[2].timestamp, 3 * day);
+        assert!((backtrader.get_asset(&symbols[0]).unwrap().positions - 3.75).abs() < 1e-9);
+        assert!((backtrader.get_asset(&symbols[1]).unwrap().positions - 7.5).abs() < 1e-9);
+
+        let report = backtrader.calculate_performance(false)?;
+        assert!((report.aggregate.final_value - 1500.0).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_signals_trade_on_transitions() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (110.0, 111.0, 109.0, 110.0),
+            (80.0, 81.0, 79.0, 80.0),
+            (80.0, 81.0, 79.0, 80.0),
+            (80.0, 81.0, 79.0, 80.0),
+        ])?;
+        // Set on every bar but the fourth
+        let signal = || [col("timestamp").cast(DataType::Int64).neq(lit(180_000)).alias("signal")];
+        let symbol = "BTCUSDT".to_string();
+
+        // As events the repeated entries buy half the equity once, the fourth bar is no exit
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_position_sizer(FixedFraction { fraction: 0.5 });
+        backtrader.set_data(&symbol, data.clone());
+        backtrader.backtest(Some(symbol.clone()), Strategy::new([] as [Expr; 0], signal()), DateRange::default())?;
+        let fills = backtrader.fills();
+        assert_eq!(fills.len(), 2);
+        assert_eq!((fills[0].timestamp, fills[0].quantity), (60_000, 5.0));
+        // Entering again after the gap tops the position up to half of the equity of 900
+        assert_eq!((fills[1].side, fills[1].quantity), (Side::Buy, 0.625));
+
+        // As states the position follows the signal, long, flat and long again
+        let mut strategy = Strategy::new([] as [Expr; 0], signal());
+        strategy.set_signal_kind(SignalKind::State);
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+        let fills = backtrader.fills();
+        assert_eq!(fills.len(), 3);
+        assert_eq!((fills[0].side, fills[0].price, fills[0].quantity), (Side::Buy, 100.0, 10.0));
+        assert_eq!((fills[1].side, fills[1].price, fills[1].quantity), (Side::Sell, 80.0, 10.0));
+        assert_eq!((fills[2].side, fills[2].price, fills[2].quantity), (Side::Buy, 80.0, 10.0));
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().cash, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_going_flat_cancels_resting_entry() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 96.0, 100.0), // Does not reach the limit
+            (97.0, 98.0, 90.0, 92.0),    // Trades through the limit
+        ])?;
+
+        // Long on the first bar only, with an entry limit below the market
+        let mut strategy = Strategy::new(
+            [] as [Expr; 0],
+            [
+                col("timestamp").cast(DataType::Int64).eq(lit(0)).alias("signal"),
+                lit("limit").alias("order_type"),
+                lit(95.0).alias("limit_price"),
+            ],
+        );
+        strategy.set_signal_kind(SignalKind::State);
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        // The target went flat before the limit was reached, so the entry was withdrawn
+        assert!(backtrader.fills().is_empty());
+        assert!(backtrader.order_book().pending(&symbol).is_empty());
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().positions, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit_event_does_not_short_on_margin() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+        ])?;
+        let bar = || col("timestamp").cast(DataType::Int64);
+        // Enters on the first bar, exits on the second and exits again on the fourth with nothing left to sell
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [when(bar().eq(lit(0)))
+                .then(lit(1))
+                .when(bar().eq(lit(60_000)).or(bar().eq(lit(180_000))))
+                .then(lit(-1))
+                .otherwise(lit(0))
+                .alias("signal")],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_margin(&symbol, MarginConfig {
+            initial_margin: 0.5,
+            maintenance_margin: 0.25,
+            borrow_rate: 0.0,
+        });
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        // The exit sells the leveraged long and stops at flat
+        let fills = backtrader.fills();
+        assert_eq!(fills.len(), 2);
+        assert_eq!((fills[0].side, fills[0].quantity), (Side::Buy, 20.0));
+        assert_eq!((fills[1].side, fills[1].quantity), (Side::Sell, 20.0));
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().positions, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ternary_and_exposure_signals() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+        ])?;
+        let bar = || col("timestamp").cast(DataType::Int64);
+        let symbol = "BTCUSDT".to_string();
+
+        // Buys on the first bar and sells on the second
+        let ternary = Strategy::new(
+            [] as [Expr; 0],
+            [when(bar().eq(lit(0))).then(lit(1)).when(bar().eq(lit(60_000))).then(lit(-1)).otherwise(lit(0)).alias("signal")],
+        );
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_data(&symbol, data.clone());
+        backtrader.backtest(Some(symbol.clone()), ternary, DateRange::default())?;
+        let fills = backtrader.fills();
+        assert_eq!((fills[0].side, fills[1].side), (Side::Buy, Side::Sell));
+        assert_eq!(backtrader.ledger().trades().len(), 1);
+
+        // Half of the equity long, then a quarter
+        let exposure = Strategy::new(
+            [] as [Expr; 0],
+            [when(bar().eq(lit(0))).then(lit(0.5)).otherwise(lit(0.25)).alias("signal")],
+        );
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_data(&symbol, data.clone());
+        backtrader.backtest(Some(symbol.clone()), exposure, DateRange::default())?;
+        let fills = backtrader.fills();
+        assert_eq!(fills.len(), 2);
+        assert_eq!((fills[0].side, fills[0].quantity), (Side::Buy, 5.0));
+        assert_eq!((fills[1].side, fills[1].quantity), (Side::Sell, 2.5));
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().positions, 2.5);
+
+        // Exposure outside [-1, 1] is refused before the backtest runs
+        let leveraged = Strategy::new([] as [Expr; 0], [lit(2.0).alias("signal")]);
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_data(&symbol, data);
+        assert!(backtrader.backtest(Some(symbol.clone()), leveraged, DateRange::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_flat_exposure_cancels_resting_order() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 96.0, 100.0), // Does not reach the limit
+            (97.0, 98.0, 90.0, 92.0),    // Trades through the limit
+        ])?;
+
+        // Half of the equity on the first bar, bought with a limit below the market, then nothing
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [
+                when(col("timestamp").cast(DataType::Int64).eq(lit(0))).then(lit(0.5)).otherwise(lit(0.0)).alias("signal"),
+                lit("limit").alias("order_type"),
+                lit(95.0).alias("limit_price"),
+            ],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        // The buy for the old target was cancelled before the limit was reached
+        assert!(backtrader.fills().is_empty());
+        assert!(backtrader.order_book().pending(&symbol).is_empty());
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().positions, 0.0);
+        Ok(())
+    }
+
+    // Buys one unit whenever the price dropped 10 below its last entry
+    #[derive(Default)]
+    struct DipBuyer {
+        started: bool,
+        finished: bool,
+        last_entry: Option<f64>,
+        fills: Vec<f64>,
+        rejected: usize,
+    }
+
+    impl EventStrategy for DipBuyer {
+        fn on_start(&mut self, context: &mut StrategyContext) {
+            self.started = context.position() == 0.0;
+        }
+
+        fn on_bar(&mut self, candle: &Candle, context: &mut StrategyContext) {
+            if self.last_entry.is_none_or(|entry| candle.close <= entry - 10.0) && context.pending().is_empty() {
+                context.submit(Order { quantity: Some(1.0), ..Order::market(context.symbol, Side::Buy) });
+                self.last_entry = Some(candle.close);
+            }
+        }
+
+        fn on_fill(&mut self, fill: &Fill, _context: &mut StrategyContext) {
+            self.fills.push(fill.price);
+        }
+
+        fn on_order_rejected(&mut self, _rejection: &Rejection, _context: &mut StrategyContext) {
+            self.rejected += 1;
+        }
+
+        fn on_finish(&mut self, context: &mut StrategyContext) {
+            self.finished = context.position() == self.fills.len() as f64;
+        }
+    }
+
+    #[test]
+    fn test_event_strategy_callbacks() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 94.0, 95.0),
+            (95.0, 96.0, 87.0, 88.0),
+            (88.0, 91.0, 87.0, 90.0),
+            (90.0, 91.0, 74.0, 75.0),
+            (75.0, 76.0, 74.0, 75.0),
+        ])?;
+        let symbol = "BTCUSDT".to_string();
+
+        let mut strategy = DipBuyer::default();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_data(&symbol, data.clone());
+        backtrader.backtest_events(Some(symbol.clone()), &mut strategy, DateRange::default())?;
+        assert!(strategy.started && strategy.finished);
+        assert_eq!(strategy.fills, vec![100.0, 88.0, 75.0]);
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().cash, 737.0);
+        assert_eq!(backtrader.equity_curve(&symbol)?.height(), 6);
+
+        // Below a notional of 90 the later entries are refused and reported back
+        let mut strategy = DipBuyer::default();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_rules(&symbol, SymbolRules { min_notional: 90.0, ..SymbolRules::UNCONSTRAINED });
+        backtrader.set_data(&symbol, data.clone());
+        backtrader.backtest_events(Some(symbol.clone()), &mut strategy, DateRange::default())?;
+        assert_eq!(strategy.fills, vec![100.0]);
+        assert_eq!(strategy.rejected, 2);
+
+        // Streaming the same candles runs the same bars and builds up the asset's data as they arrive
+        let mut strategy = DipBuyer::default();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        let mut stream = CandleStream::replay(&data)?;
+        futures::executor::block_on(backtrader.backtest_stream(&symbol, &mut stream, &mut strategy, DateRange::default()))?;
+        assert!(strategy.started && strategy.finished);
+        assert_eq!(strategy.fills, vec![100.0, 88.0, 75.0]);
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().get_data().as_ref().unwrap().height(), 6);
+        assert_eq!(backtrader.equity_curve(&symbol)?.height(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_position_sizer_targets_fraction_of_equity() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+        ])?;
+
+        // Signals on every bar, the sizer's target is only bought once
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [col("close").gt(lit(0.0)).alias("signal")],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_position_sizer(FixedFraction { fraction: 0.5 });
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        let asset = backtrader.get_asset(&symbol).unwrap();
+        assert_eq!(asset.positions, 5.0);
+        assert_eq!(asset.cash, 500.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_exits_close_positions_intrabar() -> PolarsResult<()> {
+        // Buys on the first bar, the position is entered at 100 on the second bar's open
+        let run = |bars: &[(f64, f64, f64, f64)], rules: ExitRules| -> PolarsResult<Backtrader> {
+            let strategy = Strategy::new(
+                [] as [Expr; 0],
+                [col("timestamp").cast(DataType::Int64).eq(lit(0)).alias("signal")],
+            );
+            let symbol = "BTCUSDT".to_string();
+            let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+            backtrader.set_exit_rules(rules);
+            backtrader.set_data(&symbol, candles(bars)?);
+            backtrader.backtest(Some(symbol), strategy, DateRange::default())?;
+            Ok(backtrader)
+        };
+
+        // The target is reached before the stop, which is cancelled with it
+        let backtrader = run(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 102.0, 99.0, 101.0),
+            (101.0, 111.0, 100.0, 108.0),
+            (108.0, 109.0, 90.0, 91.0),
+        ], ExitRules {
+            stop_loss: Some(ExitLevel::Percent(0.05)),
+            take_profit: Some(ExitLevel::Percent(0.1)),
+            ..ExitRules::default()
+        })?;
+        let exit = &backtrader.exits()[0];
+        assert_eq!(exit.reason, ExitReason::TakeProfit);
+        assert_eq!(exit.quantity, 10.0);
+        assert_eq!(backtrader.fills()[1].order_id, EXIT_ORDER_ID);
+        assert!((backtrader.fills()[1].price - 110.0).abs() < 1e-9);
+        assert_eq!(backtrader.exits().len(), 1);
+        assert!((backtrader.ledger().trades()[0].pnl.unwrap() - 100.0).abs() < 1e-9);
+
+        // The trailing stop follows the high of 120 and is hit at 108
+        let trending = [
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 105.0, 99.0, 104.0),
+            (104.0, 120.0, 103.0, 118.0),
+            (118.0, 119.0, 107.0, 110.0),
+        ];
+        let backtrader = run(&trending, ExitRules { trailing_stop: Some(0.1), ..ExitRules::default() })?;
+        let exit = &backtrader.exits()[0];
+        assert_eq!(exit.reason, ExitReason::TrailingStop);
+        assert!((exit.price - 108.0).abs() < 1e-9);
+
+        // Two bars after the entry bar the position is closed at the open
+        let backtrader = run(&trending, ExitRules { max_bars: Some(2), ..ExitRules::default() })?;
+        let exit = &backtrader.exits()[0];
+        assert_eq!(exit.reason, ExitReason::TimeExit);
+        assert_eq!(exit.price, 118.0);
+        assert_eq!(backtrader.get_asset("BTCUSDT").unwrap().positions, 0.0);
+
+        // Entered at the open of 100, the same bar then falls through the stop at 95
+        let backtrader = run(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 93.0, 94.0),
+            (94.0, 95.0, 93.0, 94.0),
+        ], ExitRules { stop_loss: Some(ExitLevel::Percent(0.05)), ..ExitRules::default() })?;
+        let exit = &backtrader.exits()[0];
+        assert_eq!(exit.reason, ExitReason::StopLoss);
+        assert_eq!(exit.timestamp, 60_000);
+        assert!((exit.price - 95.0).abs() < 1e-9);
+        assert_eq!(backtrader.get_asset("BTCUSDT").unwrap().positions, 0.0);
+        Ok(())
+    }
+
+    struct BuySecondBar;
+
+    impl EventStrategy for BuySecondBar {
+        fn on_bar(&mut self, candle: &Candle, context: &mut StrategyContext) {
+            if candle.timestamp == 60_000 {
+                context.submit(Order { quantity: Some(1.0), ..Order::market(context.symbol, Side::Buy) });
+            }
+        }
+    }
+
+    #[test]
+    fn test_event_backtest_places_atr_exits() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0), // Entered at the open with an ATR of 2 over the two bars before
+            (100.0, 100.5, 97.0, 98.0),
+        ])?;
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_exit_rules(ExitRules {
+            stop_loss: Some(ExitLevel::AtrMultiple { multiple: 1.0, window: 2 }),
+            ..ExitRules::default()
+        });
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest_events(Some(symbol.clone()), &mut BuySecondBar, DateRange::default())?;
+
+        let exit = &backtrader.exits()[0];
+        assert_eq!(exit.reason, ExitReason::StopLoss);
+        assert_eq!(exit.price, 98.0);
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().positions, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_perpetual_settles_funding_on_open_position() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+            (100.0, 101.0, 99.0, 100.0),
+        ])?;
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [lit(false).alias("signal")],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_margin(&symbol, MarginConfig {
+            initial_margin: 0.5,
+            maintenance_margin: 0.25,
+            borrow_rate: 0.0,
+        });
+        // The first funding is before the position opens, the short then receives 0.1% and pays 0.05%
+        let funding = FundingRates::from_dataframe(&df!(
+            "timestamp" => [0i64, 30_000, 120_000],
+            "funding_rate" => [0.01, 0.001, -0.0005],
+        )?)?;
+        backtrader.set_funding_rates(&symbol, funding);
+        backtrader.set_data(&symbol, data);
+        backtrader.order_book_mut().submit(Order::market(&symbol, Side::Sell), 0);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        let payments = backtrader.funding_payments();
+        assert_eq!(payments.len(), 2);
+        assert_eq!(payments[0].timestamp, 30_000);
+        assert_eq!(payments[0].position, -20.0);
+        assert!((payments[0].amount - 2.0).abs() < 1e-9);
+        assert!((payments[1].amount + 1.0).abs() < 1e-9);
+
+        let asset = backtrader.get_asset(&symbol).unwrap();
+        assert!((asset.funding_pnl - 1.0).abs() < 1e-9);
+        assert!((asset.cash - 3001.0).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_is_liquidated_at_maintenance_margin() -> PolarsResult<()> {
+        let data = candles(&[
+            (100.0, 101.0, 99.0, 100.0),
+            (110.0, 125.0, 109.0, 115.0), // Trades through the liquidation price of 120
+            (115.0, 116.0, 114.0, 115.0),
+        ])?;
+
+        let strategy = Strategy::new(
+            [] as [Expr; 0],
+            [lit(false).alias("signal")],
+        );
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        backtrader.set_margin(&symbol, MarginConfig {
+            initial_margin: 0.5,
+            maintenance_margin: 0.25,
+            borrow_rate: 0.0,
+        });
+        backtrader.set_data(&symbol, data);
+        // Short with 2x of the equity on the first open
+        backtrader.order_book_mut().submit(Order::market(&symbol, Side::Sell), 0);
+        backtrader.backtest(Some(symbol.clone()), strategy, DateRange::default())?;
+
+        let fills = backtrader.fills();
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[0].quantity, 20.0);
+        assert_eq!(fills[1].order_id, LIQUIDATION_ORDER_ID);
+        assert_eq!(fills[1].price, 120.0);
+
+        let liquidation = &backtrader.liquidations()[0];
+        assert_eq!(liquidation.quantity, -20.0);
+        assert_eq!(liquidation.equity, 600.0);
+
+        let asset = backtrader.get_asset(&symbol).unwrap();
+        assert_eq!(asset.positions, 0.0);
+        assert_eq!(asset.cash, 600.0);
+
+        let trade = &backtrader.ledger().trades()[0];
+        assert_eq!(trade.side, Side::Sell);
+        assert_eq!(trade.entry_price, 100.0);
+        assert_eq!(trade.exit_price, Some(120.0));
+        assert_eq!(trade.pnl, Some(-400.0));
+
+        // Equity is recorded for every bar with its timestamp
+        let equity = backtrader.equity_curve(&symbol)?;
+        assert_eq!(equity.height(), 3);
+        assert_eq!(equity.column("equity")?.f64()?.get(2), Some(600.0));
+        let underwater = backtrader.underwater_curve(&symbol)?;
+        assert_eq!(underwater.get_column_names(), ["timestamp", "drawdown"]);
+        assert_eq!(underwater.column("drawdown")?.as_materialized_series().min::<f64>()?, Some(-0.4));
+
+        let report = backtrader.calculate_performance(false)?;
+        assert_eq!(report.aggregate.final_value, 600.0);
+        assert_eq!(report.aggregate.total_return, -0.4);
+        assert_eq!(report.aggregate.trade_count, 1);
+        assert_eq!(report.aggregate.win_rate, 0.0);
+        assert_eq!(report.symbols[&symbol].exposure_time, 1.0 / 3.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_symbols_load_from_their_data_sources() -> PolarsResult<()> {
+        let day = 86_400_000;
+        let symbols = ["BTCUSDT".to_string(), "ETHUSDT".to_string()];
+        let mut sources = HashMap::new();
+        for (i, symbol) in symbols.iter().enumerate() {
+            let price = 100.0 * (i + 1) as f64;
+            let mut data = candles_every(&[(price, price, price, price); 4], day)?;
+            let path = std::env::temp_dir().join(format!("{}_source_test.parquet", symbol));
+            ParquetWriter::new(File::create(&path)?).finish(&mut data)?;
+            sources.insert(symbol.clone(), DataSource {
+                exchange: Some("binance".to_string()),
+                timeframe: Some("1d".to_string()),
+                start_date: Some("1970-01-02".to_string()),
+                ..DataSource::new(path.to_str().unwrap())
+            });
+        }
+
+        let strategy = Strategy::new([] as [Expr; 0], [lit(true).alias("signal")]);
+        let mut backtrader = Backtrader::with_exchange(1000.0, &ExchangeProfile::binance(), symbols.iter().collect())?;
+        backtrader.set_data_sources(sources.clone());
+        backtrader.backtest(None, strategy, DateRange::default())?;
+        for symbol in &symbols {
+            // The first day is before the start date
+            assert_eq!(backtrader.equity_curve(symbol)?.height(), 3);
+        }
+        // A symbol that is not in the portfolio has no data to load
+        let strategy = Strategy::new([] as [Expr; 0], [lit(true).alias("signal")]);
+        assert!(backtrader.backtest(Some("UNKNOWN".to_string()), strategy, DateRange::default()).is_err());
+
+        // Candles recorded on another exchange are refused rather than traded with Binance's rules
+        let mut backtrader = Backtrader::with_exchange(1000.0, &ExchangeProfile::binance(), vec![&symbols[0]])?;
+        backtrader.set_data_source(&symbols[0], DataSource { exchange: Some("kraken".to_string()), ..sources[&symbols[0]].clone() });
+        let strategy = Strategy::new([] as [Expr; 0], [lit(true).alias("signal")]);
+        assert!(backtrader.backtest(None, strategy, DateRange::default()).is_err());
+
+        for source in sources.values() {
+            std::fs::remove_file(&source.path)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_backtest_within_date_range() -> PolarsResult<()> {
+        let data = candles_every(&[(100.0, 100.0, 100.0, 100.0); 5], 86_400_000)?;
+        let strategy = Strategy::new([] as [Expr; 0], [lit(true).alias("signal")]);
+
+        let symbol = "BTCUSDT".to_string();
+        let mut backtrader = Backtrader::new(1000.0, 0.0, 0.0, vec![&symbol]);
+        assert!(DateRange::parse(Some("1970-01-04"), Some("1970-01-02")).is_err());
+        let range = DateRange::parse(Some("1970-01-02"), Some("1970-01-03"))?;
+        backtrader.set_data(&symbol, data);
+        backtrader.backtest(Some(symbol.clone()), strategy, range)?;
+
+        // Both days of the range are traded, the end date included, and the first signal fills on the next open
+        let equity = backtrader.equity_curve(&symbol)?;
+        assert_eq!(equity.height(), 2);
+        assert_eq!(backtrader.fills()[0].timestamp, 2 * 86_400_000);
+
+        // The range filters a copy, a later run over all dates still sees every bar
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().get_data().as_ref().unwrap().height(), 5);
+        backtrader.backtest(Some(symbol.clone()), Strategy::new([] as [Expr; 0], [lit(true).alias("signal")]), DateRange::default())?;
+        // and starts over instead of continuing the first run
+        let equity = backtrader.equity_curve(&symbol)?;
+        assert_eq!(equity.height(), 5);
+        assert_eq!(backtrader.fills().len(), 1);
+        assert_eq!(backtrader.fills()[0].timestamp, 86_400_000);
+        assert_eq!(backtrader.get_asset(&symbol).unwrap().history.len(), 5);
+
+        // Event driven backtests take the range the same way
+        backtrader.backtest_events(Some(symbol.clone()), &mut DipBuyer::default(), range)?;
+        assert_eq!(backtrader.equity_curve(&symbol)?.height(), 2);
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn data_source_present() {
         let data_source = "examples/data/btcusd_1-min_data.csv";
         if !std::path::Path::new(data_source).exists() {
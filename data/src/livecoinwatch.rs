@@ -103,10 +103,7 @@ impl Coin {
             Err(())
         }.expect("TODO: panic message");
 
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "An error occurred",
-        )))
+        Err(Box::new(std::io::Error::other("An error occurred")))
     }
 }
 
@@ -1,21 +1,18 @@
-use std::collections::HashMap;
 use polars::error::PolarsResult;
-use polars::export::arrow::array::iterator::ArrayAccessor;
-use polars::export::arrow::pushable::Pushable;
 use polars::prelude::{col, RollingOptionsFixedWindow};
 use Backtester::data::data::DataHandler;
-use Backtester::data::data::DataHandlerFetch;
 use Backtester::strategy::strategy::Strategy;
 use Backtester::strategy::strategy::StrategyTrait;
 
 fn main() -> PolarsResult<()> {
     println!("Booting strategy!");
-    let _symbol = "BTCUSDT";
-    let _start_date = "2023-01-01";
-    let _end_date = "2023-12-31";
+    let symbol = "BTCUSDT";
+    let start_date = "2023-01-01";
+    let end_date = "2023-12-31";
     // TODO implement ticker! just a simple todo as if thats simple at all....
 
-    let mut data = DataHandler::load_data("/Users/wexoah/RustroverProjects/Quant-trader/TA_Lib/examples/data/AMZN.csv");
+    let handler = DataHandler::new(symbol, start_date, end_date);
+    let mut data = handler.load("/Users/wexoah/RustroverProjects/Quant-trader/TA_Lib/examples/data/AMZN.csv")?;
 
     let window_20 = RollingOptionsFixedWindow {
         window_size: 3,